@@ -4,8 +4,8 @@ use base64::Engine;
 use base64::engine::general_purpose;
 use chrono::{Duration, Utc};
 use database::models::{
-    create_user, create_user_sessions, find_by_email_user, find_by_hash_tokens, find_by_id_user,
-    insert_tokens, revoke,
+    STARTING_BALANCE, create_user, create_user_sessions, find_by_email_user, find_by_hash_tokens,
+    find_by_id_user, grant_wallet, insert_tokens, revoke, wallet_balance,
 };
 use rand::{TryRngCore, rngs::OsRng};
 use serde::{Deserialize, Serialize};
@@ -50,7 +50,7 @@ fn generate_refresh_token() -> anyhow::Result<String> {
     OsRng
         .try_fill_bytes(&mut buffer)
         .map_err(|e| anyhow::anyhow!("failed to get randomness: {}", e))?;
-    Ok(general_purpose::URL_SAFE_NO_PAD.encode(&buffer))
+    Ok(general_purpose::URL_SAFE_NO_PAD.encode(buffer))
 }
 
 fn hash_refresh(token: &str) -> String {
@@ -103,10 +103,13 @@ pub async fn signup(
     }
     let hashed = hash_password(&payload.password)
         .await
-        .map_err(|e| ServiceError::ExternalError(e))?;
+        .map_err(ServiceError::ExternalError)?;
     let user_id = create_user(&app.pool, &email, &hashed, payload.display_name.as_deref())
         .await //as_ref -> Option<&String> as_deref -> Option<&str>
         .map_err(|e| ServiceError::DataBaseError(e.to_string()))?;
+    grant_wallet(&app.pool, user_id, STARTING_BALANCE)
+        .await
+        .map_err(|e| ServiceError::DataBaseError(e.to_string()))?;
 
     let session_id = create_user_sessions(&app.pool, user_id, payload.device_name.as_deref())
         .await
@@ -118,11 +121,11 @@ pub async fn signup(
         app.setting.access_token_exp,
         &app.setting.jwt_secret,
     )
-    .map_err(|e| ServiceError::ExternalError(e))?;
+    .map_err(ServiceError::ExternalError)?;
 
     let refresh_plain = generate_refresh_token()?;
     let refresh_hash = hash_refresh(&refresh_plain);
-    let expiry_at = (Utc::now() + Duration::seconds(app.setting.refresh_token_exp)).into();
+    let expiry_at = Utc::now() + Duration::seconds(app.setting.refresh_token_exp);
     insert_tokens(&app.pool, user_id, &refresh_hash, expiry_at)
         .await
         .map_err(|e| ServiceError::DataBaseError(e.to_string()))?;
@@ -133,7 +136,7 @@ pub async fn signup(
         expires_in: app.setting.access_token_exp,
     };
 
-    Ok(HttpResponse::Ok().cookie(cookie).json(body)).map_err(|e| ServiceError::ExternalError(e))
+    Ok(HttpResponse::Ok().cookie(cookie).json(body)).map_err(ServiceError::ExternalError)
 }
 
 pub async fn login(
@@ -156,7 +159,7 @@ pub async fn login(
 
     let session_id = create_user_sessions(&app.pool, user.id, payload.device_name.as_deref())
         .await
-        .map_err(|e| ServiceError::ExternalError(e))?;
+        .map_err(ServiceError::ExternalError)?;
 
     let access_token = create_access_token(
         &user.id.to_string(),
@@ -164,11 +167,11 @@ pub async fn login(
         app.setting.access_token_exp,
         &app.setting.jwt_secret,
     )
-    .map_err(|e| ServiceError::ExternalError(e))?;
+    .map_err(ServiceError::ExternalError)?;
 
     let refresh_plain = generate_refresh_token()?;
     let refresh_hash = hash_refresh(&refresh_plain);
-    let expires_at = (Utc::now() + Duration::seconds(app.setting.refresh_token_exp)).into();
+    let expires_at = Utc::now() + Duration::seconds(app.setting.refresh_token_exp);
     insert_tokens(&app.pool, user.id, &refresh_hash, expires_at)
         .await
         .map_err(|e| ServiceError::DataBaseError(e.to_string()))?;
@@ -209,16 +212,16 @@ pub async fn refresh_token(
     }
     revoke(&app.pool, rec.id)
         .await
-        .map_err(|e| ServiceError::ExternalError(e))?;
+        .map_err(ServiceError::ExternalError)?;
 
     let user_id = rec.user_id;
 
     let new_plain_token = generate_refresh_token()?;
     let new_hashed = hash_refresh(&new_plain_token);
-    let expires_at_new = (Utc::now() + Duration::seconds(app.setting.refresh_token_exp)).into();
+    let expires_at_new = Utc::now() + Duration::seconds(app.setting.refresh_token_exp);
     insert_tokens(&app.pool, user_id, &new_hashed, expires_at_new)
         .await
-        .map_err(|e| ServiceError::ExternalError(e))?;
+        .map_err(ServiceError::ExternalError)?;
 
     let access_token = create_access_token(
         &user_id.to_string(),
@@ -226,7 +229,7 @@ pub async fn refresh_token(
         app.setting.access_token_exp,
         &app.setting.jwt_secret,
     )
-    .map_err(|e| ServiceError::ExternalError(e))?;
+    .map_err(ServiceError::ExternalError)?;
 
     let cookie = build_refresh_cookie(&app.setting, &new_plain_token);
     let body = AccessTokenResponse {
@@ -255,11 +258,11 @@ pub async fn logout(
         let hashed = hash_refresh(&plain);
         if let Some(r) = find_by_hash_tokens(&app.pool, &hashed)
             .await
-            .map_err(|e| ServiceError::ExternalError(e))?
+            .map_err(ServiceError::ExternalError)?
         {
             revoke(&app.pool, r.id)
                 .await
-                .map_err(|e| ServiceError::ExternalError(e))?;
+                .map_err(ServiceError::ExternalError)?;
         }
     }
     let clear = build_clear_cookie(&app.setting);
@@ -277,12 +280,16 @@ pub async fn me(app: web::Data<AppState>, req: HttpRequest) -> Result<HttpRespon
     let user = find_by_id_user(&app.pool, user_id)
         .await
         .map_err(|e| ServiceError::DataBaseError(e.to_string()))?;
+    let balance = wallet_balance(&app.pool, user_id)
+        .await
+        .map_err(|e| ServiceError::DataBaseError(e.to_string()))?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!(
         {
             "id" : user.id,
             "email" : user.email,
             "display_name" : user.display_name,
+            "balance" : balance,
             "created_at" : user.created_at
         }
     )))
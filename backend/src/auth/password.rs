@@ -16,11 +16,9 @@ pub async fn hash_password(password: &str) -> anyhow::Result<String> {
         Ok(password_hash.to_string())
     });
 
-    let inner_result = handle
+    handle
         .await
-        .map_err(|e| anyhow::anyhow!("spawn_blocking join error: {}", e))?;
-
-    inner_result
+        .map_err(|e| anyhow::anyhow!("spawn_blocking join error: {}", e))?
 }
 
 pub async fn verify_password(hash: &str, candidate: &str) -> bool {
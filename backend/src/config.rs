@@ -5,12 +5,13 @@ use std::env;
 pub struct Setting {
     pub database_url: String,
     pub jwt_secret: SecretString,
-    pub default_max_connections: i16,
+    pub default_max_connections: u32,
     pub access_token_exp: i64,
     pub refresh_token_exp: i64,
     pub bind_addr: String,
     pub redis_url: String,
     pub worker_threads: usize,
+    pub debug_rooms: bool, //lets rooms marked debug deal from seeded or stacked decks
 }
 
 impl Setting {
@@ -21,7 +22,7 @@ impl Setting {
         let jwt_secret = env::var("SECRET_KEY").map(SecretString::from)?; //this doesn't allow unintentional pr accidental access of secrets
         let default_max_connections = env::var("DEFAULT_MAX_CONNECTIONS")
             .unwrap_or_else(|_| "20".into())
-            .parse::<i16>()?;
+            .parse::<u32>()?;
         let access_token_exp = env::var("ACCESS_TOKEN_EXP")
             .unwrap_or_else(|_| "900".into())
             .parse::<i64>()?;
@@ -33,7 +34,10 @@ impl Setting {
         let worker_threads = env::var("WORKER_THREADS")
             .ok()
             .and_then(|s| s.parse::<usize>().ok())
-            .unwrap_or_else(|| num_cpus::get());
+            .unwrap_or_else(num_cpus::get);
+        let debug_rooms = env::var("DEBUG_ROOMS")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
 
         Ok(Self {
             database_url,
@@ -44,6 +48,7 @@ impl Setting {
             bind_addr,
             redis_url,
             worker_threads,
+            debug_rooms,
         })
     }
 }
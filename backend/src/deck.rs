@@ -0,0 +1,128 @@
+use std::fmt::Debug;
+
+use crate::{
+    fairness::{ServerSeed, shuffled_deck},
+    poker_engine::{Card, GameVariant, new_deck},
+};
+
+// where the cards of a hand come from. the game manager asks for the next server seed
+// when a hand is committed to and for the deck when it is dealt
+pub trait DeckSource: Debug + Send + Sync {
+    fn name(&self) -> &'static str; // stored on the hand as deck_source
+
+    fn server_seed(&self) -> ServerSeed;
+
+    fn deck(&self, variant: GameVariant, seed: &[u8; 32]) -> Vec<Card>;
+
+    // true when the seeds can't rebuild the deck, the hand then stores the whole deck
+    fn records_deck(&self) -> bool {
+        false
+    }
+}
+
+// what real tables use, a fresh secret seed every hand
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SecureShuffle;
+
+impl DeckSource for SecureShuffle {
+    fn name(&self) -> &'static str {
+        "secure"
+    }
+
+    fn server_seed(&self) -> ServerSeed {
+        ServerSeed::generate()
+    }
+
+    fn deck(&self, variant: GameVariant, seed: &[u8; 32]) -> Vec<Card> {
+        shuffled_deck(variant, seed)
+    }
+}
+
+// the same server seed every hand, with the same client seeds every hand deals the same cards
+#[derive(Debug, Clone)]
+pub struct SeededShuffle {
+    seed: ServerSeed,
+}
+
+impl SeededShuffle {
+    pub fn new(seed: ServerSeed) -> Self {
+        SeededShuffle { seed }
+    }
+}
+
+impl DeckSource for SeededShuffle {
+    fn name(&self) -> &'static str {
+        "seeded"
+    }
+
+    fn server_seed(&self) -> ServerSeed {
+        self.seed.clone()
+    }
+
+    fn deck(&self, variant: GameVariant, seed: &[u8; 32]) -> Vec<Card> {
+        shuffled_deck(variant, seed)
+    }
+}
+
+// cards in the exact order they are dealt, None takes any card that isn't placed elsewhere.
+// whatever the list leaves out follows it in new_deck order
+#[derive(Debug, Clone, Default)]
+pub struct StackedDeck {
+    cards: Vec<Option<Card>>,
+}
+
+impl StackedDeck {
+    pub fn new(cards: Vec<Card>) -> Self {
+        StackedDeck {
+            cards: cards.into_iter().map(Some).collect(),
+        }
+    }
+
+    // hole cards for the dealt players in seat order, then up to five board cards.
+    // follows the flop game deal: every hole card first, then burn and flop, burn and turn,
+    // burn and river
+    pub fn flop_game(hole_cards: &[Vec<Card>], board: &[Card]) -> Self {
+        let mut cards: Vec<Option<Card>> = hole_cards.iter().flatten().copied().map(Some).collect();
+        for street in [
+            &board[..board.len().min(3)],
+            board.get(3..4).unwrap_or(&[]),
+            board.get(4..5).unwrap_or(&[]),
+        ] {
+            if street.is_empty() {
+                break;
+            }
+            cards.push(None);
+            cards.extend(street.iter().copied().map(Some));
+        }
+        StackedDeck { cards }
+    }
+}
+
+impl DeckSource for StackedDeck {
+    fn name(&self) -> &'static str {
+        "stacked"
+    }
+
+    // nothing is shuffled, the seed only keeps the commit-reveal flow the same
+    fn server_seed(&self) -> ServerSeed {
+        ServerSeed::from_bytes([0; 32])
+    }
+
+    fn deck(&self, variant: GameVariant, _seed: &[u8; 32]) -> Vec<Card> {
+        let placed: Vec<Card> = self.cards.iter().flatten().copied().collect();
+        let mut rest = new_deck(variant)
+            .into_iter()
+            .filter(|c| !placed.contains(c));
+        let mut deck: Vec<Card> = self
+            .cards
+            .iter()
+            .filter_map(|c| c.or_else(|| rest.next()))
+            .collect();
+        deck.extend(rest);
+        deck
+    }
+
+    fn records_deck(&self) -> bool {
+        true
+    }
+}
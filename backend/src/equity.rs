@@ -0,0 +1,298 @@
+use rand::{
+    SeedableRng, distr::Distribution, distr::weighted::WeightedIndex, rngs::StdRng,
+    seq::SliceRandom,
+};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    evaluator::{self, Strength},
+    poker_engine::{Card, GameVariant, new_deck},
+    range::Range,
+};
+
+pub const MAX_PLAYERS: usize = 10;
+// past this many runouts an exact answer takes too long for a request, so we sample instead
+pub const EXACT_LIMIT: u64 = 2_000_000;
+pub const DEFAULT_SAMPLES: u64 = 200_000;
+pub const MAX_SAMPLES: u64 = 2_000_000;
+// tries at drawing one hand per range without two of them sharing a card
+const MAX_REDRAWS: usize = 1_000;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EquityError {
+    #[error("equity needs between 2 and {MAX_PLAYERS} players, got {0}")]
+    PlayerCount(usize),
+    #[error("card {0} is used more than once")]
+    Duplicate(Card),
+    #[error("a board has at most 5 cards, got {0}")]
+    BoardSize(usize),
+    #[error("not enough cards left in the deck to deal every hand")]
+    DeckExhausted,
+    #[error("range of player {0} has no hands left once the board and dead cards are removed")]
+    EmptyRange(usize),
+    #[error("the ranges can not be dealt together without sharing cards")]
+    NoValidDeal,
+}
+
+// all figures are percentages of the runouts looked at
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerEquity {
+    pub win: f64,
+    pub tie: f64,
+    pub lose: f64,
+    pub equity: f64, // wins plus each tie split between the players sharing it
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EquityResult {
+    pub players: Vec<PlayerEquity>,
+    pub exact: bool,
+    pub runouts: u64,
+    pub seed: Option<u64>, // only set for monte carlo, running again with it gives the same numbers
+}
+
+// weighted by how likely the hands that produced them are
+#[derive(Debug, Clone, Default)]
+struct Tally {
+    wins: f64,
+    ties: f64,
+    share: f64,
+}
+
+// hands are known hole cards or None for a player whose cards are unknown
+pub fn calculate(
+    hands: &[Option<[Card; 2]>],
+    board: &[Card],
+    dead: &[Card],
+    samples: u64,
+    seed: Option<u64>,
+) -> Result<EquityResult, EquityError> {
+    let mut seen = Vec::new();
+    for &card in hands.iter().flatten().flatten() {
+        if seen.contains(&card) || board.contains(&card) || dead.contains(&card) {
+            return Err(EquityError::Duplicate(card));
+        }
+        seen.push(card);
+    }
+    let ranges: Vec<Range> = hands
+        .iter()
+        .map(|h| h.map_or_else(Range::any, Range::from_cards))
+        .collect();
+    calculate_ranges(&ranges, board, dead, samples, seed)
+}
+
+pub fn calculate_ranges(
+    ranges: &[Range],
+    board: &[Card],
+    dead: &[Card],
+    samples: u64,
+    seed: Option<u64>,
+) -> Result<EquityResult, EquityError> {
+    if ranges.len() < 2 || ranges.len() > MAX_PLAYERS {
+        return Err(EquityError::PlayerCount(ranges.len()));
+    }
+    if board.len() > 5 {
+        return Err(EquityError::BoardSize(board.len()));
+    }
+
+    let mut known = Vec::new();
+    for &card in board.iter().chain(dead) {
+        if known.contains(&card) {
+            return Err(EquityError::Duplicate(card));
+        }
+        known.push(card);
+    }
+    let mut ranges = ranges.to_vec();
+    for (i, range) in ranges.iter_mut().enumerate() {
+        range.remove_blocked(&known);
+        if range.is_empty() {
+            return Err(EquityError::EmptyRange(i));
+        }
+    }
+    let deck: Vec<Card> = new_deck(GameVariant::Holdem)
+        .into_iter()
+        .filter(|c| !known.contains(c))
+        .collect();
+    let missing = 5 - board.len();
+    let hole_cards = ranges.len() * 2;
+    if deck.len() < hole_cards + missing {
+        return Err(EquityError::DeckExhausted);
+    }
+
+    let mut deal = Deal {
+        hands: vec![[deck[0]; 2]; ranges.len()],
+        board: board.to_vec(),
+        tallies: vec![Tally::default(); ranges.len()],
+        runouts: 0,
+        weight: 0.0,
+    };
+
+    let hand_deals = ranges
+        .iter()
+        .fold(1u64, |acc, r| acc.saturating_mul(r.len() as u64));
+    let runouts = hand_deals.saturating_mul(combinations(deck.len() - hole_cards, missing));
+    let exact = runouts <= EXACT_LIMIT;
+    let seed = if exact {
+        deal.enumerate_hands(&ranges, &deck, 0, 1.0);
+        None
+    } else {
+        let seed = seed.unwrap_or_else(rand::random);
+        deal.sample(&ranges, &deck, samples.clamp(1, MAX_SAMPLES), seed)?;
+        Some(seed)
+    };
+    if deal.weight <= 0.0 {
+        return Err(EquityError::NoValidDeal);
+    }
+
+    let players = deal
+        .tallies
+        .iter()
+        .map(|t| {
+            let win = t.wins * 100.0 / deal.weight;
+            let tie = t.ties * 100.0 / deal.weight;
+            PlayerEquity {
+                win,
+                tie,
+                lose: 100.0 - win - tie,
+                equity: t.share * 100.0 / deal.weight,
+            }
+        })
+        .collect();
+
+    Ok(EquityResult {
+        players,
+        exact,
+        runouts: deal.runouts,
+        seed,
+    })
+}
+
+struct Deal {
+    hands: Vec<[Card; 2]>,
+    board: Vec<Card>,
+    tallies: Vec<Tally>,
+    runouts: u64,
+    weight: f64,
+}
+
+impl Deal {
+    fn holds(&self, players: usize, cards: &[Card]) -> bool {
+        self.hands[..players]
+            .iter()
+            .any(|h| h.iter().any(|c| cards.contains(c)))
+    }
+
+    // every combo of each range in turn that does not clash with the ones before it,
+    // then every board on top of them
+    fn enumerate_hands(&mut self, ranges: &[Range], deck: &[Card], player: usize, weight: f64) {
+        if player == ranges.len() {
+            let rest: Vec<Card> = deck
+                .iter()
+                .copied()
+                .filter(|c| !self.holds(player, &[*c]))
+                .collect();
+            return self.enumerate_board(&rest, 0, weight);
+        }
+        for combo in ranges[player].combos() {
+            if self.holds(player, &combo.cards) {
+                continue;
+            }
+            self.hands[player] = combo.cards;
+            self.enumerate_hands(ranges, deck, player + 1, weight * combo.weight);
+        }
+    }
+
+    fn enumerate_board(&mut self, deck: &[Card], from: usize, weight: f64) {
+        if self.board.len() == 5 {
+            return self.record(weight);
+        }
+        for i in from..deck.len() {
+            self.board.push(deck[i]);
+            self.enumerate_board(deck, i + 1, weight);
+            self.board.pop();
+        }
+    }
+
+    fn sample(
+        &mut self,
+        ranges: &[Range],
+        deck: &[Card],
+        samples: u64,
+        seed: u64,
+    ) -> Result<(), EquityError> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let pickers = ranges
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                WeightedIndex::new(r.combos().iter().map(|c| c.weight))
+                    .map_err(|_| EquityError::EmptyRange(i))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let known_board = self.board.len();
+
+        for _ in 0..samples {
+            // redraw every hand on a clash so each deal keeps the odds its weights give it
+            let mut redraws = 0;
+            'draw: loop {
+                for (player, picker) in pickers.iter().enumerate() {
+                    let combo = ranges[player].combos()[picker.sample(&mut rng)];
+                    if self.holds(player, &combo.cards) {
+                        redraws += 1;
+                        if redraws == MAX_REDRAWS {
+                            return Err(EquityError::NoValidDeal);
+                        }
+                        continue 'draw;
+                    }
+                    self.hands[player] = combo.cards;
+                }
+                break;
+            }
+
+            let mut rest: Vec<Card> = deck
+                .iter()
+                .copied()
+                .filter(|c| !self.holds(ranges.len(), &[*c]))
+                .collect();
+            let (drawn, _) = rest.partial_shuffle(&mut rng, 5 - known_board);
+            self.board.truncate(known_board);
+            self.board.extend_from_slice(drawn);
+            self.record(1.0);
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, weight: f64) {
+        let strengths: Vec<Strength> = self
+            .hands
+            .iter()
+            .map(|h| {
+                let mut cards = [h[0]; 7];
+                cards[1] = h[1];
+                cards[2..].copy_from_slice(&self.board);
+                evaluator::evaluate(&cards)
+            })
+            .collect();
+        let best = strengths.iter().copied().max().unwrap_or(0);
+        let winners = strengths.iter().filter(|&&s| s == best).count();
+        for (tally, &s) in self.tallies.iter_mut().zip(&strengths) {
+            if s != best {
+                continue;
+            }
+            if winners == 1 {
+                tally.wins += weight;
+            } else {
+                tally.ties += weight;
+            }
+            tally.share += weight / winners as f64;
+        }
+        self.runouts += 1;
+        self.weight += weight;
+    }
+}
+
+// how many ways `cards` more can be dealt from a deck of `deck`
+fn combinations(deck: usize, cards: usize) -> u64 {
+    (0..cards).fold(1u128, |acc, i| acc * (deck - i) as u128 / (i + 1) as u128) as u64
+}
@@ -17,8 +17,6 @@ pub enum ServiceError {
     Conflict(String),
     #[error("ValidationError: {0}")]
     ValidationError(String),
-    #[error("Service Unavailable")]
-    ServiceUnavailable,
     #[error("DataBase Error: ")]
     DataBaseError(String),
     #[error("Internal Server Error")]
@@ -56,7 +54,6 @@ impl ResponseError for ServiceError {
             ServiceError::NotFound(_) => StatusCode::NOT_FOUND,
             ServiceError::Conflict(_) => StatusCode::CONFLICT,
             ServiceError::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
-            ServiceError::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
             ServiceError::DataBaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             ServiceError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
             ServiceError::ExternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
@@ -74,9 +71,6 @@ impl ResponseError for ServiceError {
             ServiceError::ExternalError(_) => {
                 error!("External Dependency Error: {}", self)
             }
-            ServiceError::ServiceUnavailable => {
-                error!("Service temporarily Error:{}", self)
-            }
             _ => {
                 debug!("Client Error: {}", self)
             }
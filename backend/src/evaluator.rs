@@ -0,0 +1,318 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+};
+
+use once_cell::sync::Lazy;
+
+use crate::poker_engine::{Card, HandRank, HandRules, Suit, evaluate_five_with};
+
+// every rank gets a prime so the product of a hand's ranks identifies its rank multiset
+const PRIMES: [u64; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+const SUITS: [Suit; 4] = [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades];
+
+// there are 7462 distinct five card hands, strength 1 is 7-5-4-3-2 offsuit and 7462 a royal flush.
+// comparing two strengths gives the same answer as comparing their HandRanks.
+// strengths only compare within one set of HandRules, each has its own tables
+pub type Strength = u16;
+
+struct Tables {
+    flush: Vec<Strength>, // indexed by the 13 bit rank mask of one suit
+    unsuited: HashMap<u64, Strength, Mix>, // keyed by the prime product of 5, 6 or 7 ranks
+    ranks: Vec<HandRank>, // strength -> HandRank, index 0 unused
+}
+
+// prime products are already unique, they only need spreading over the buckets
+#[derive(Default)]
+struct MixHasher(u64);
+
+impl Hasher for MixHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = n.wrapping_mul(0x9E37_79B9_7F4A_7C15).rotate_left(29);
+    }
+}
+
+type Mix = BuildHasherDefault<MixHasher>;
+
+static TABLES: Lazy<Tables> = Lazy::new(|| build_tables(HandRules::STANDARD));
+static SHORT_DECK_TABLES: Lazy<Tables> = Lazy::new(|| build_tables(HandRules::SHORT_DECK));
+
+fn tables(rules: HandRules) -> &'static Tables {
+    if rules == HandRules::SHORT_DECK {
+        &SHORT_DECK_TABLES
+    } else {
+        &TABLES
+    }
+}
+
+pub fn evaluate(cards: &[Card]) -> Strength {
+    evaluate_with(cards, HandRules::STANDARD)
+}
+
+pub fn evaluate_with(cards: &[Card], rules: HandRules) -> Strength {
+    let tables = tables(rules);
+    let mut suit_masks = [0u16; 4];
+    let mut product = 1u64;
+    for c in cards {
+        let r = (c.rank - 2) as usize;
+        suit_masks[c.suit as usize] |= 1 << r;
+        product *= PRIMES[r];
+    }
+    let mut best = tables.unsuited.get(&product).copied().unwrap_or(0);
+    for mask in suit_masks {
+        if mask.count_ones() >= 5 {
+            best = best.max(tables.flush[mask as usize]);
+        }
+    }
+    best
+}
+
+// omaha hands play exactly two hole cards with exactly three board cards
+pub fn evaluate_omaha(hole: &[Card], board: &[Card]) -> Strength {
+    let mut best = 0;
+    for (i, &a) in hole.iter().enumerate() {
+        for &b in &hole[i + 1..] {
+            for (j, &c) in board.iter().enumerate() {
+                for (k, &d) in board.iter().enumerate().skip(j + 1) {
+                    for &e in &board[k + 1..] {
+                        best = best.max(evaluate(&[a, b, c, d, e]));
+                    }
+                }
+            }
+        }
+    }
+    best
+}
+
+pub fn hand_rank(strength: Strength) -> HandRank {
+    hand_rank_with(strength, HandRules::STANDARD)
+}
+
+pub fn hand_rank_with(strength: Strength, rules: HandRules) -> HandRank {
+    tables(rules).ranks[strength as usize].clone()
+}
+
+fn build_tables(rules: HandRules) -> Tables {
+    // every distinct five card hand, as the ranks it uses and whether it is suited
+    let mut classes = Vec::new();
+    for_each_multiset(5, &mut |counts| {
+        let ranks = expand(counts);
+        let suited = ranks.len() == 5 && counts.iter().all(|&c| c <= 1);
+        classes.push((
+            evaluate_five_with(&five_cards(&ranks, false), rules),
+            ranks.clone(),
+            false,
+        ));
+        if suited {
+            classes.push((
+                evaluate_five_with(&five_cards(&ranks, true), rules),
+                ranks,
+                true,
+            ));
+        }
+    });
+    classes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut ranks = vec![classes[0].0.clone()];
+    let mut flush = vec![0; 1 << 13];
+    let mut unsuited = HashMap::default();
+    for (rank, hand, suited) in classes {
+        // hands the rules can't tell apart share a strength
+        if ranks.len() == 1 || ranks.last() != Some(&rank) {
+            ranks.push(rank);
+        }
+        let strength = (ranks.len() - 1) as Strength;
+        if suited {
+            flush[mask_of(&hand) as usize] = strength;
+        } else {
+            unsuited.insert(product_of(&hand), strength);
+        }
+    }
+
+    // six and seven suited cards play their best five
+    for mask in 0u16..(1 << 13) {
+        if mask.count_ones() > 5 {
+            flush[mask as usize] = bits_of(mask)
+                .into_iter()
+                .map(|bit| flush[(mask & !(1 << bit)) as usize])
+                .max()
+                .unwrap_or(0);
+        }
+    }
+    // same for six and seven unsuited cards, one rank dropped at a time
+    for size in [6, 7] {
+        for_each_multiset(size, &mut |counts| {
+            let hand = expand(counts);
+            let product = product_of(&hand);
+            let best = (0..13)
+                .filter(|&r| counts[r] > 0)
+                .map(|r| unsuited[&(product / PRIMES[r])])
+                .max()
+                .unwrap_or(0);
+            unsuited.insert(product, best);
+        });
+    }
+
+    Tables {
+        flush,
+        unsuited,
+        ranks,
+    }
+}
+
+// calls f with the count of each rank for every multiset of `size` ranks, at most four of a kind
+fn for_each_multiset(size: usize, f: &mut dyn FnMut(&[usize; 13])) {
+    fn go(rank: usize, left: usize, counts: &mut [usize; 13], f: &mut dyn FnMut(&[usize; 13])) {
+        if rank == 13 {
+            if left == 0 {
+                f(counts);
+            }
+            return;
+        }
+        for c in 0..=left.min(4) {
+            counts[rank] = c;
+            go(rank + 1, left - c, counts, f);
+        }
+        counts[rank] = 0;
+    }
+    go(0, size, &mut [0; 13], f);
+}
+
+fn expand(counts: &[usize; 13]) -> Vec<usize> {
+    (0..13)
+        .flat_map(|r| std::iter::repeat_n(r, counts[r]))
+        .collect()
+}
+
+// real cards for a rank multiset, spread over the suits unless suited is asked for
+fn five_cards(ranks: &[usize], suited: bool) -> [Card; 5] {
+    let mut seen = [0usize; 13];
+    std::array::from_fn(|i| {
+        let r = ranks[i];
+        let suit = if suited {
+            Suit::Spades
+        } else if i == 4 && seen[r] == 0 {
+            // four distinct ranks already went to one suit, keep the fifth off it
+            Suit::Hearts
+        } else {
+            SUITS[seen[r]]
+        };
+        seen[r] += 1;
+        Card {
+            rank: r as u8 + 2,
+            suit,
+        }
+    })
+}
+
+fn product_of(ranks: &[usize]) -> u64 {
+    ranks.iter().map(|&r| PRIMES[r]).product()
+}
+
+fn mask_of(ranks: &[usize]) -> u16 {
+    ranks.iter().fold(0, |m, &r| m | 1 << r)
+}
+
+fn bits_of(mask: u16) -> Vec<u16> {
+    (0..13).filter(|b| mask & (1 << b) != 0).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_cards;
+    use crate::poker_engine::{GameVariant, five_card_hands, new_deck};
+
+    fn rank_of(cards: &str) -> HandRank {
+        hand_rank(evaluate(&parse_cards(cards).unwrap()))
+    }
+
+    #[test]
+    fn strengths_span_every_distinct_hand() {
+        assert_eq!(evaluate(&parse_cards("7c5d4h3s2c").unwrap()), 1);
+        assert_eq!(evaluate(&parse_cards("AsKsQsJsTs").unwrap()), 7462);
+    }
+
+    #[test]
+    fn categories() {
+        let hands = [
+            ("AsKsQsJsTs", 8),
+            ("9h9d9c9s2d", 7),
+            ("3h3d3cKsKd", 6),
+            ("Ah9h7h4h2h", 5),
+            ("5h4d3c2sAd", 4),
+            ("QhQdQc9s2d", 3),
+            ("JhJd4c4s2d", 2),
+            ("ThTd8c4s2d", 1),
+            ("Ah9d7c4s2d", 0),
+        ];
+        for (cards, category) in hands {
+            assert_eq!(rank_of(cards).category(), category, "{}", cards);
+        }
+    }
+
+    #[test]
+    fn wheel_is_the_lowest_straight() {
+        assert!(rank_of("5h4d3c2sAd") < rank_of("6h5d4c3s2d"));
+        assert!(rank_of("5h4d3c2sAd") > rank_of("AhAdAcKsQd"));
+    }
+
+    #[test]
+    fn kickers_break_ties() {
+        assert!(rank_of("AhAd9c7s2d") > rank_of("AcAs9h6d5c"));
+        assert_eq!(rank_of("AhAd9c7s2d"), rank_of("AcAs9h7d2c"));
+    }
+
+    #[test]
+    fn seven_cards_play_the_best_five() {
+        assert_eq!(rank_of("AhKh2c2d2s9c7d"), rank_of("2c2d2sAhKh"));
+        assert_eq!(rank_of("AhKhQhJhTh2c2d"), rank_of("AsKsQsJsTs"));
+    }
+
+    #[test]
+    fn matches_the_direct_evaluation() {
+        let deck = new_deck(GameVariant::Holdem);
+        // seven cards at a time, a fixed walk through the deck
+        for start in 0..deck.len() {
+            let cards = (0..7)
+                .map(|k| deck[(start + k * 11) % deck.len()])
+                .collect::<Vec<_>>();
+            let best = five_card_hands(&cards)
+                .iter()
+                .map(|five| evaluate_five_with(five, HandRules::STANDARD))
+                .max()
+                .unwrap();
+            assert_eq!(hand_rank(evaluate(&cards)), best);
+        }
+    }
+
+    #[test]
+    fn omaha_plays_two_hole_cards() {
+        let board = parse_cards("2h5h9h8hTc").unwrap();
+        let flush = parse_cards("AhKhQdJd").unwrap();
+        assert_eq!(hand_rank(evaluate_omaha(&flush, &board)).category(), 5);
+        // one heart in the hand makes no flush, whatever the board holds
+        let straight = parse_cards("AhKcQdJd").unwrap();
+        assert_eq!(hand_rank(evaluate_omaha(&straight, &board)).category(), 4);
+    }
+
+    #[test]
+    fn short_deck_flush_beats_full_house() {
+        let rules = HandRules::SHORT_DECK;
+        let rank =
+            |cards: &str| hand_rank_with(evaluate_with(&parse_cards(cards).unwrap(), rules), rules);
+        assert!(rank("Ah9h7h8hJh") > rank("6h6d6cKsKd"));
+        // A-6-7-8-9 is the lowest straight
+        assert!(rank("Ah6d7c8s9d") > rank("AhAdAcKsQd"));
+    }
+}
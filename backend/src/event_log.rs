@@ -0,0 +1,67 @@
+use std::collections::VecDeque;
+
+use uuid::Uuid;
+
+use crate::{game_manager::OutgoingEvent, views::TableSnapshot};
+
+pub const EVENT_BUFFER: usize = 256; // events kept per room for clients catching up
+
+#[derive(Debug, Clone)]
+pub struct LoggedEvent {
+    pub event: OutgoingEvent, // without "table", that is projected per recipient
+    pub table: Option<TableSnapshot>,
+}
+
+impl LoggedEvent {
+    pub fn view_for(&self, viewer: Option<Uuid>) -> OutgoingEvent {
+        let mut event = self.event.clone();
+        if let (Some(table), Some(obj)) = (&self.table, event.payload.as_object_mut()) {
+            obj.insert(
+                "table".to_string(),
+                serde_json::json!(table.view_for(viewer)),
+            );
+        }
+        event
+    }
+}
+
+// the last EVENT_BUFFER events of one room. seq starts at 1 and has no gaps, so a client
+// that saw seq n is missing exactly the events after n
+#[derive(Debug, Default)]
+pub struct EventLog {
+    last_seq: u64,
+    events: VecDeque<LoggedEvent>,
+}
+
+impl EventLog {
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    pub fn next_seq(&mut self) -> u64 {
+        self.last_seq += 1;
+        self.last_seq
+    }
+
+    pub fn push(&mut self, event: LoggedEvent) {
+        if self.events.len() == EVENT_BUFFER {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    // None when some of the missed events already fell out of the buffer
+    pub fn since(&self, seq: u64) -> Option<Vec<&LoggedEvent>> {
+        if seq > self.last_seq {
+            return None;
+        }
+        let first = self
+            .events
+            .front()
+            .map_or(self.last_seq + 1, |e| e.event.seq);
+        if seq + 1 < first {
+            return None;
+        }
+        Some(self.events.iter().filter(|e| e.event.seq > seq).collect())
+    }
+}
@@ -0,0 +1,132 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::poker_engine::{Card, GameVariant, new_deck};
+
+// commit-reveal shuffling, so players can check no deck was stacked:
+//   1. the server picks a 32 byte seed for the next hand and publishes sha256(seed) as hex
+//   2. players may set a client seed while that commitment is out
+//   3. the hand is dealt from sha256(hex(server seed) + ":" + client seeds joined by ":"),
+//      client seeds in seat order
+//   4. once the hand is over the server seed is revealed
+// the shuffle only uses sha256 so anyone can rebuild the deck without this code:
+// block n of the stream is sha256(seed || n as 8 byte big endian), read as 4 byte big endian
+// numbers. a fisher-yates pass from the top of new_deck swaps card i with card
+// (number % (i + 1)), skipping numbers at or above (2^32 - 1) - (2^32 - 1) % (i + 1)
+
+pub const MAX_CLIENT_SEED_LEN: usize = 64;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SeedError {
+    #[error("client seed must be 1 to {MAX_CLIENT_SEED_LEN} printable characters without ':'")]
+    InvalidClientSeed,
+    #[error("server seed must be 64 hex characters")]
+    InvalidServerSeed,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerSeed {
+    seed: [u8; 32],
+    pub hash: String, // the commitment, safe to publish before the hand
+}
+
+impl ServerSeed {
+    pub fn generate() -> Self {
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        Self::from_bytes(seed)
+    }
+
+    pub fn from_bytes(seed: [u8; 32]) -> Self {
+        ServerSeed {
+            seed,
+            hash: hex::encode(Sha256::digest(seed)),
+        }
+    }
+
+    pub fn from_hex(seed: &str) -> Result<Self, SeedError> {
+        let bytes = hex::decode(seed).map_err(|_| SeedError::InvalidServerSeed)?;
+        let seed: [u8; 32] = bytes.try_into().map_err(|_| SeedError::InvalidServerSeed)?;
+        Ok(Self::from_bytes(seed))
+    }
+
+    // only to be published once the hand it dealt is over
+    pub fn reveal(&self) -> String {
+        hex::encode(self.seed)
+    }
+}
+
+pub fn validate_client_seed(seed: &str) -> Result<(), SeedError> {
+    let valid = !seed.is_empty()
+        && seed.len() <= MAX_CLIENT_SEED_LEN
+        && seed.chars().all(|c| c.is_ascii_graphic() && c != ':');
+    if valid {
+        Ok(())
+    } else {
+        Err(SeedError::InvalidClientSeed)
+    }
+}
+
+pub fn combined_seed(server: &ServerSeed, client_seeds: &[String]) -> [u8; 32] {
+    let mut hash = Sha256::new();
+    hash.update(server.reveal().as_bytes());
+    for seed in client_seeds {
+        hash.update(b":");
+        hash.update(seed.as_bytes());
+    }
+    hash.finalize().into()
+}
+
+pub fn shuffled_deck(variant: GameVariant, seed: &[u8; 32]) -> Vec<Card> {
+    let mut deck = new_deck(variant);
+    let mut stream = SeedStream::new(*seed);
+    for i in (1..deck.len()).rev() {
+        let j = stream.below(i as u32 + 1) as usize;
+        deck.swap(i, j);
+    }
+    deck
+}
+
+struct SeedStream {
+    seed: [u8; 32],
+    counter: u64,
+    block: [u8; 32],
+    used: usize,
+}
+
+impl SeedStream {
+    fn new(seed: [u8; 32]) -> Self {
+        SeedStream {
+            seed,
+            counter: 0,
+            block: [0; 32],
+            used: 32,
+        }
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        if self.used == 32 {
+            let mut hash = Sha256::new();
+            hash.update(self.seed);
+            hash.update(self.counter.to_be_bytes());
+            self.block = hash.finalize().into();
+            self.counter += 1;
+            self.used = 0;
+        }
+        let n = u32::from_be_bytes(self.block[self.used..self.used + 4].try_into().unwrap());
+        self.used += 4;
+        n
+    }
+
+    // uniform in 0..n, rejecting the top of the range that would favour low numbers
+    fn below(&mut self, n: u32) -> u32 {
+        let zone = u32::MAX - u32::MAX % n;
+        loop {
+            let x = self.next_u32();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+}
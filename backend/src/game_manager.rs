@@ -1,70 +1,227 @@
-use redis::AsyncCommands;
-use redis::RedisError;
 use chrono::Utc;
 use dashmap::DashMap;
 use database::models::{
-    add_player, create_hand, finish_hand, hand_players, insert_action, insert_player,
-    remove_players,
+    Rooms, add_player, create_hand, find_by_id_rooms, finish_hand, insert_action, insert_player,
+    post_buy_in, post_cash_out, post_hand_result, remove_players, reveal_hand_seed, set_connected,
+    update_chips, wallet_balance,
 };
 use redis::aio::ConnectionManager;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use std::{sync::Arc, time::Duration, usize};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::sync::*;
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 use crate::{
     config::Setting,
-    poker_engine::{Card, HandRank, evaluate_best_of_seven, new_deck, shuffle_deck},
-    ws_server::{ClientInfo, Outgoing},
+    deck::{DeckSource, SecureShuffle},
+    event_log::{EventLog, LoggedEvent},
+    fairness::{ServerSeed, combined_seed, validate_client_seed},
+    lobby::listing::lobby_tables,
+    notation::cards_to_json,
+    poker_engine::{
+        BettingLimit, BettingRound, BlindPositions, Card, GameVariant, HandRank, LegalAction,
+        LowRank, PlayerAction, Street, award_pots, award_pots_hi_lo, blind_positions, build_pots,
+        burn_card, deal_flop, deal_river, deal_turn,
+    },
+    rake::RakePolicy,
+    views::TableSnapshot,
+    ws_server::{ClientInfo, Outgoing, ServerEvent},
 };
 
+// lets players see the last result and a joining socket register before the deal
+const NEXT_HAND_DELAY: Duration = Duration::from_secs(3);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutgoingEvent {
+    pub seq: u64, // per room, see EventLog
     pub event_type: String,
     pub room_id: Uuid,
     pub payload: serde_json::Value,
     pub emitted_at: i64,
 }
+pub const MAX_SEATS: usize = 10;
+pub const DEFAULT_MAX_PLAYERS: usize = 6;
+pub const ROOM_CLOSED: &str = "finished"; //room_status of a closed table
+const AUTO_SIT_OUT_TIMEOUTS: u8 = 2; // timeouts in a row that sit a player out
+
+// chosen before the player's turn and taken for them as soon as action reaches them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreAction {
+    CheckFold,
+    CallAny,
+}
+
+impl PreAction {
+    fn action(self, legal: &[LegalAction]) -> PlayerAction {
+        let can = |wanted: fn(&LegalAction) -> bool| legal.iter().any(wanted);
+        match self {
+            _ if can(|a| *a == LegalAction::Check) => PlayerAction::Check,
+            PreAction::CheckFold => PlayerAction::Fold,
+            PreAction::CallAny if can(|a| matches!(a, LegalAction::Call { .. })) => {
+                PlayerAction::Call
+            }
+            // calling would take more than the stack
+            PreAction::CallAny => PlayerAction::AllIn,
+        }
+    }
+}
+
+// who decided an action, the timer and pre-actions act for the player
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ActionSource {
+    Player,
+    PreAction,
+    Timeout,
+}
+
 #[derive(Debug, Clone)]
 pub struct PlayerSlot {
     pub user_id: Uuid,
-    pub seat: usize,
     pub chips: i64,
     pub connected: bool,
+    pub owes_big_blind: bool, //joined after the button passed, posts a big blind to get dealt in
+    pub client_seed: Option<String>, //mixed into the shuffle of every hand the player is dealt into
+    pub sitting_out: bool,    //keeps the seat but isn't dealt in
+    pub time_bank: Duration,  //used once the action clock runs out, refilled every few hands
+    pub timeouts: u8,         //turns in a row the timer acted for the player
+    pub pre_action: Option<PreAction>, //cleared once taken and when the hand ends
+    pub leaving: bool,        //stood up during a hand they are still in, cashed out once it ends
+}
+
+// stud games have no blinds, small_blind is the bring-in and big_blind the bet it completes to.
+// fixed limit bets big_blind on the early streets and twice that from the turn / fifth street on
+#[derive(Debug, Clone)]
+pub struct RoomConfig {
+    pub debug: bool, //may swap in a seeded or stacked deck when the server allows debug rooms
+    pub variant: GameVariant,
+    pub betting_limit: BettingLimit,
+    pub raise_cap: usize,
+    pub small_blind: i64,
+    pub big_blind: i64,
+    pub ante: i64,
+    pub min_buy_in: i64,
+    pub max_buy_in: i64,
+    pub action_clock: Duration,
+    pub time_bank: Duration,
+    pub time_bank_refill_hands: u64, //0 never refills
+    pub rake: RakePolicy,
+}
+
+impl RoomConfig {
+    // the smallest bet on a street, and the only size of bet or raise in fixed limit
+    pub fn bet_size(&self, street: Street) -> i64 {
+        match (self.betting_limit, street) {
+            (
+                BettingLimit::FixedLimit,
+                Street::Turn | Street::River | Street::Fifth | Street::Sixth | Street::Seventh,
+            ) => self.big_blind * 2,
+            _ => self.big_blind,
+        }
+    }
+}
+
+impl Default for RoomConfig {
+    fn default() -> Self {
+        RoomConfig {
+            debug: false,
+            variant: GameVariant::Holdem,
+            betting_limit: BettingLimit::NoLimit,
+            raise_cap: 4,
+            small_blind: 5,
+            big_blind: 10,
+            ante: 0,
+            min_buy_in: 200,
+            max_buy_in: 1000,
+            action_clock: Duration::from_secs(30),
+            time_bank: Duration::from_secs(60),
+            time_bank_refill_hands: 20,
+            rake: RakePolicy::default(),
+        }
+    }
+}
+
+impl From<&Rooms> for RoomConfig {
+    fn from(room: &Rooms) -> Self {
+        let variant = GameVariant::from_name(&room.variant).unwrap_or_default();
+        RoomConfig {
+            debug: room.debug,
+            variant,
+            // rooms without a structure play the one their variant is usually played with
+            betting_limit: room
+                .betting_limit
+                .as_deref()
+                .and_then(BettingLimit::from_name)
+                .unwrap_or(variant.betting_limit()),
+            raise_cap: room.raise_cap.max(1) as usize,
+            small_blind: room.small_blind,
+            big_blind: room.big_blind,
+            ante: room.ante,
+            min_buy_in: room.min_buy_in,
+            max_buy_in: room.max_buy_in,
+            action_clock: Duration::from_secs(room.action_clock_secs.max(1) as u64),
+            time_bank: Duration::from_secs(room.time_bank_secs.max(0) as u64),
+            time_bank_refill_hands: room.time_bank_refill_hands.max(0) as u64,
+            rake: RakePolicy {
+                bps: room.rake_bps.max(0) as i64,
+                caps: room.rake_caps.clone(),
+                no_flop_no_drop: room.no_flop_no_drop,
+            },
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct HandState {
     pub id: Uuid,
-    pub started_at: i64,
     pub pot: i64,
-    pub board: Vec<Card>,                      //flop . turn , river cards
-    pub hole_cards: Vec<Option<(Card, Card)>>, //per seat
+    pub board: Vec<Card>,                   //flop . turn , river cards
+    pub hole_cards: Vec<Option<Vec<Card>>>, //per seat, 2 for hold'em and 4 or 5 for omaha
+    pub up_cards: Vec<Vec<Card>>,           //stud cards dealt face up, also part of hole_cards
     pub current_turn: Option<usize>,
-    pub round: String, //{current stage :"pre-flop" , "flop" , "turn" , "river"}
+    pub round: Street,
+    pub deck: Vec<Card>,            //undealt cards for the rest of the hand
     pub players_in_hand: Vec<bool>, //false means player folded
+    // seats dealt in and who sat there, the hand settles with them
+    pub players: Vec<(usize, Uuid)>,
+    pub betting: BettingRound,
+    pub server_seed: ServerSeed, //revealed when the hand is finished
+    pub client_seeds: serde_json::Value,
 }
 
 #[derive(Debug)]
 pub struct RoomState {
-    pub room_id: Uuid,
     pub max_players: usize,
+    pub config: RoomConfig,
     pub seats: Vec<Option<PlayerSlot>>,
     pub dealer_index: Option<usize>,
+    pub blinds: Option<BlindPositions>, //positions used by the last hand
     pub active_hand: Option<HandState>,
     pub turn_task: Option<CancellationToken>,
+    pub hands_dealt: u64,
+    pub next_seed: ServerSeed, //its hash is published before the hand it will shuffle
+    pub deck_source: Option<Arc<dyn DeckSource>>, //debug rooms only, overrides the manager's
+    pub closed: bool,          //set by close_room, stale handles can't seat anyone after it
 }
 impl RoomState {
-    pub fn new(room_id: Uuid, max_players: usize) -> Self {
+    pub fn new(max_players: usize, config: RoomConfig) -> Self {
         RoomState {
-            room_id: room_id,
             max_players,
+            config,
             seats: vec![None; max_players],
             dealer_index: None,
+            blinds: None,
             active_hand: None,
             turn_task: None,
+            hands_dealt: 0,
+            next_seed: ServerSeed::generate(),
+            deck_source: None,
+            closed: false,
         }
     }
 }
@@ -74,7 +231,10 @@ pub struct GameManager {
     pub pool: PgPool,
     pub redis: ConnectionManager,
     pub client_registry: Arc<DashMap<Uuid, Vec<ClientInfo>>>,
+    pub event_log: Arc<DashMap<Uuid, EventLog>>,
+    pub lobby_subscribers: Arc<DashMap<Uuid, mpsc::Sender<Outgoing>>>, //by connection id
     pub setting: Setting,
+    pub deck_source: Arc<dyn DeckSource>,
 }
 
 impl GameManager {
@@ -89,164 +249,563 @@ impl GameManager {
             pool,
             redis,
             client_registry,
+            event_log: Arc::new(DashMap::new()),
+            lobby_subscribers: Arc::new(DashMap::new()),
             setting,
+            deck_source: Arc::new(SecureShuffle),
+        }
+    }
+
+    fn room_deck_source(&self, r: &RoomState) -> Arc<dyn DeckSource> {
+        r.deck_source
+            .clone()
+            .unwrap_or_else(|| self.deck_source.clone())
+    }
+
+    // lets a debug room deal exact cards, starting with the hand after the current one.
+    // returns the commitment of that hand
+    pub async fn set_deck_source(
+        &self,
+        room_id: Uuid,
+        deck_source: Arc<dyn DeckSource>,
+    ) -> anyhow::Result<String> {
+        let room = self.ensure_room(room_id).await?;
+        let mut r = room.write().await;
+        if !self.setting.debug_rooms || !r.config.debug {
+            return Err(anyhow::anyhow!("room is not a debug room"));
         }
+        r.next_seed = deck_source.server_seed();
+        r.deck_source = Some(deck_source);
+        Ok(r.next_seed.hash.clone())
     }
 
-    pub async fn ensure_room(&self, room_id: Uuid, max_players: usize) -> Arc<RwLock<RoomState>> {
+    // loads the room's settings the first time anyone joins it after a start
+    pub async fn ensure_room(&self, room_id: Uuid) -> anyhow::Result<Arc<RwLock<RoomState>>> {
         if let Some(ev) = self.rooms.get(&room_id) {
-            ev.value().clone()
-        } else {
-            let s = Arc::new(RwLock::new(RoomState::new(room_id, max_players)));
-            self.rooms.insert(room_id, s.clone());
-            s
+            return Ok(ev.value().clone());
         }
+        let room = find_by_id_rooms(&self.pool, room_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("room not found"))?;
+        if room.room_status == ROOM_CLOSED {
+            return Err(anyhow::anyhow!("room is closed"));
+        }
+        let max_players = room
+            .max_players
+            .map_or(DEFAULT_MAX_PLAYERS, |n| (n.max(2) as usize).min(MAX_SEATS));
+        let mut state = RoomState::new(max_players, RoomConfig::from(&room));
+        state.next_seed = self.deck_source.server_seed();
+        let s = self
+            .rooms
+            .entry(room_id)
+            .or_insert_with(|| Arc::new(RwLock::new(state)))
+            .value()
+            .clone();
+        Ok(s)
     }
 
+    // the host closing a table. seated players are stood up, nobody can join it afterwards
+    pub async fn close_room(&self, room_id: Uuid) -> anyhow::Result<()> {
+        if let Some(entry) = self.rooms.get(&room_id) {
+            let mut r = entry.value().write().await;
+            if r.active_hand.is_some() {
+                return Err(anyhow::anyhow!("a hand is in progress"));
+            }
+            for i in 0..r.seats.len() {
+                if let Some(ps) = r.seats[i].take()
+                    && let Err(e) = self.cash_out(room_id, i, &ps).await
+                {
+                    r.seats[i] = Some(ps);
+                    return Err(e);
+                }
+            }
+            r.closed = true;
+        }
+        self.rooms.remove(&room_id);
+        let _ = self
+            .emit_events(room_id, "room_closed", serde_json::json!({}), None)
+            .await;
+        self.client_registry.remove(&room_id);
+        self.event_log.remove(&room_id);
+        Ok(())
+    }
+
+    // buy_in defaults to the most the table and the player's balance allow
     pub async fn join_room(
         &self,
         user_id: Uuid,
         room_id: Uuid,
         requested_seat: Option<u8>,
+        buy_in: Option<i64>,
     ) -> anyhow::Result<u8> {
-        let room = self.ensure_room(room_id, 6).await;
+        let room = self.ensure_room(room_id).await?;
         let mut r = room.write().await;
-
-        if let Some(req) = requested_seat {
-            let index = (req - 1) as usize;
-            if index < r.seats.len() && r.seats[index].is_none() {
-                r.seats[index] = Some(PlayerSlot {
-                    user_id,
-                    seat: index,
-                    chips: 1000,
-                    connected: true,
-                });
-                let _ = add_player(&self.pool, room_id, req as i16, user_id, 1000, false).await;
-                //TODO :: implement emit events
-                return Ok(req);
+        if r.closed {
+            return Err(anyhow::anyhow!("room is closed"));
+        }
+        // joining again, e.g. from a second socket, keeps the seat already taken
+        if let Some(i) = r
+            .seats
+            .iter()
+            .position(|s| s.as_ref().is_some_and(|p| p.user_id == user_id))
+        {
+            if let Some(ps) = r.seats[i].as_mut() {
+                ps.leaving = false;
             }
+            return Ok((i + 1) as u8);
         }
+        let owes_big_blind = r.dealer_index.is_some();
 
-        for (i, s) in r.seats.iter_mut().enumerate() {
-            if s.is_none() {
-                let seat_num = (i + 1) as u8;
-                *s = Some(PlayerSlot {
-                    user_id,
-                    seat: seat_num as usize,
-                    chips: 1000,
-                    connected: true,
-                });
-                let _ =
-                    add_player(&self.pool, room_id, seat_num as i16, user_id, 1000, false).await;
-                //TODO:: Implement emit event
-                return Ok(seat_num);
+        let requested = requested_seat
+            .map(|req| (req as usize).wrapping_sub(1))
+            .filter(|&i| i < r.seats.len() && r.seats[i].is_none());
+        let index = requested
+            .or_else(|| r.seats.iter().position(|s| s.is_none()))
+            .ok_or_else(|| anyhow::anyhow!("room full"))?;
+        let (min, max) = (r.config.min_buy_in, r.config.max_buy_in);
+        let chips = match buy_in {
+            Some(chips) => chips,
+            None => wallet_balance(&self.pool, user_id).await?.min(max),
+        };
+        if chips < min || chips > max {
+            return Err(anyhow::anyhow!("buy-in is {} to {} chips", min, max));
+        }
+        if !post_buy_in(&self.pool, user_id, room_id, chips).await? {
+            return Err(anyhow::anyhow!(
+                "balance too small for a {} chip buy-in",
+                chips
+            ));
+        }
+        let seat_num = (index + 1) as u8;
+        if let Err(e) =
+            add_player(&self.pool, room_id, seat_num as i16, user_id, chips, false).await
+        {
+            tracing::error!("could not seat {} in room {}: {}", user_id, room_id, e);
+            // the buy-in already went to the table
+            if let Err(e) = post_cash_out(&self.pool, user_id, room_id, chips).await {
+                tracing::error!("could not refund the buy-in of {}: {}", user_id, e);
             }
+            return Err(e);
         }
-        Err(anyhow::anyhow!("room full"))
+        r.seats[index] = Some(PlayerSlot {
+            user_id,
+            chips,
+            connected: true,
+            owes_big_blind,
+            client_seed: None,
+            sitting_out: false,
+            time_bank: r.config.time_bank,
+            timeouts: 0,
+            pre_action: None,
+            leaving: false,
+        });
+        let table = TableSnapshot::capture(&r);
+        drop(r);
+        let payload = serde_json::json!({ "seat": seat_num, "user_id": user_id, "chips": chips });
+        let _ = self
+            .emit_events(room_id, "player_joined", payload, Some(&table))
+            .await;
+        self.publish_lobby(room_id);
+        self.schedule_hand(room_id);
+        Ok(seat_num)
     }
 
-    pub async fn leave_room(&self, user_id: Uuid, room_id: Uuid) -> anyhow::Result<()> {
-        if let Some(entry) = self.rooms.get(&room_id) {
-            let mut r = entry.value().write().await;
-            for slot in r.seats.iter_mut() {
-                if let Some(ps) = slot {
-                    if ps.user_id == user_id {
-                        let _ = remove_players(&self.pool, room_id, ps.seat as i16).await;
-                        //TODO:: Implement emit event
-                        *slot = None;
-                        break;
-                    }
-                }
+    // returns the chips put back on the balance. a player still in the hand keeps their seat
+    // until it ends so what they put in the pot can still win, returns None then
+    pub async fn leave_room(&self, user_id: Uuid, room_id: Uuid) -> anyhow::Result<Option<i64>> {
+        let Some(entry) = self.rooms.get(&room_id) else {
+            return Ok(Some(0));
+        };
+        let mut r = entry.value().write().await;
+        let Some(index) = r
+            .seats
+            .iter()
+            .position(|s| s.as_ref().is_some_and(|p| p.user_id == user_id))
+        else {
+            return Ok(Some(0));
+        };
+        let in_hand = r
+            .active_hand
+            .as_ref()
+            .is_some_and(|hs| hs.players_in_hand[index]);
+        if in_hand {
+            if let Some(ps) = r.seats[index].as_mut() {
+                ps.leaving = true;
             }
+            // the timer checks or folds for a leaving player, no need to wait out the clock
+            let their_turn = r
+                .active_hand
+                .as_ref()
+                .is_some_and(|hs| hs.current_turn == Some(index));
+            drop(r);
+            if their_turn {
+                self.spawn_turn_timer(room_id)?;
+            }
+            return Ok(None);
+        }
+        let Some(ps) = r.seats[index].take() else {
+            return Ok(Some(0));
+        };
+        if let Err(e) = self.cash_out(room_id, index, &ps).await {
+            r.seats[index] = Some(ps);
+            return Err(e);
+        }
+        let table = TableSnapshot::capture(&r);
+        drop(r);
+        let payload =
+            serde_json::json!({ "seat": index + 1, "user_id": user_id, "chips": ps.chips });
+        let _ = self
+            .emit_events(room_id, "player_left", payload, Some(&table))
+            .await;
+        self.publish_lobby(room_id);
+        Ok(Some(ps.chips))
+    }
+
+    // the stack goes back on the balance before the seat is freed. an error means nothing was
+    // paid out and the player keeps the seat
+    async fn cash_out(&self, room_id: Uuid, index: usize, ps: &PlayerSlot) -> anyhow::Result<()> {
+        if ps.chips > 0 {
+            post_cash_out(&self.pool, ps.user_id, room_id, ps.chips).await?;
+        }
+        // paid out by now, a row left behind doesn't seat anybody again
+        if let Err(e) = remove_players(&self.pool, room_id, (index + 1) as i16).await {
+            tracing::error!("could not free seat {} of {}: {}", index + 1, room_id, e);
         }
         Ok(())
     }
 
+    // a rebuy once the stack is gone, a top-up otherwise. never during a hand the player is in,
+    // and never above the table's max buy-in. returns the new stack
+    pub async fn add_chips(
+        &self,
+        user_id: Uuid,
+        room_id: Uuid,
+        amount: i64,
+    ) -> anyhow::Result<i64> {
+        let entry = self
+            .rooms
+            .get(&room_id)
+            .ok_or_else(|| anyhow::anyhow!("room not found"))?;
+        let mut r = entry.value().write().await;
+        if r.closed {
+            return Err(anyhow::anyhow!("room is closed"));
+        }
+        let index = r
+            .seats
+            .iter()
+            .position(|s| s.as_ref().is_some_and(|p| p.user_id == user_id))
+            .ok_or_else(|| anyhow::anyhow!("player not found"))?;
+        if r.active_hand
+            .as_ref()
+            .is_some_and(|hs| hs.players_in_hand[index])
+        {
+            return Err(anyhow::anyhow!("chips can only be added between hands"));
+        }
+        let (min, max) = (r.config.min_buy_in, r.config.max_buy_in);
+        let chips = r.seats[index].as_ref().map_or(0, |ps| ps.chips);
+        if amount <= 0 || chips + amount > max {
+            return Err(anyhow::anyhow!(
+                "the stack can be topped up to {} chips",
+                max
+            ));
+        }
+        if chips == 0 && amount < min {
+            return Err(anyhow::anyhow!("a rebuy is at least {} chips", min));
+        }
+        if !post_buy_in(&self.pool, user_id, room_id, amount).await? {
+            return Err(anyhow::anyhow!("balance too small to add {} chips", amount));
+        }
+        let chips = chips + amount;
+        if let Some(ps) = r.seats[index].as_mut() {
+            ps.chips = chips;
+        }
+        let _ = update_chips(&self.pool, room_id, chips, (index + 1) as i16).await;
+        let table = TableSnapshot::capture(&r);
+        drop(r);
+        let payload = serde_json::json!({ "seat": index + 1, "added": amount, "chips": chips });
+        let _ = self
+            .emit_events(room_id, "chips_added", payload, Some(&table))
+            .await;
+        self.schedule_hand(room_id);
+        Ok(chips)
+    }
+
+    // deals the next hand after NEXT_HAND_DELAY if the table is ready for one by then
+    pub fn schedule_hand(&self, room_id: Uuid) {
+        let gm = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(NEXT_HAND_DELAY).await;
+            if let Err(e) = gm.start_hand_if_ready(room_id).await {
+                tracing::warn!("could not start a hand in room {}: {}", room_id, e);
+            }
+        });
+    }
+
+    // two players with chips who aren't sitting out, and no hand already running
+    async fn start_hand_if_ready(&self, room_id: Uuid) -> anyhow::Result<Option<Uuid>> {
+        let Some(entry) = self.rooms.get(&room_id) else {
+            return Ok(None);
+        };
+        let ready = {
+            let r = entry.value().read().await;
+            let players = r
+                .seats
+                .iter()
+                .flatten()
+                .filter(|ps| ps.chips > 0 && !ps.sitting_out)
+                .count();
+            r.active_hand.is_none() && players >= 2
+        };
+        drop(entry);
+        if !ready {
+            return Ok(None);
+        }
+        self.start_hand(room_id).await.map(Some)
+    }
+
     pub async fn start_hand(&self, room_id: Uuid) -> anyhow::Result<Uuid> {
         let entry = self
             .rooms
             .get(&room_id)
-            .ok_or_else(|| anyhow::anyhow!("room not found"))
-            .unwrap();
+            .ok_or_else(|| anyhow::anyhow!("room not found"))?;
         let mut r = entry.value().write().await;
-        let mut deck = new_deck();
-        shuffle_deck(&mut deck);
+        if r.active_hand.is_some() {
+            return Err(anyhow::anyhow!("a hand is already running"));
+        }
+        // players without chips or sitting out aren't dealt in
+        let active = r
+            .seats
+            .iter()
+            .map(|slot| {
+                slot.as_ref()
+                    .map(|ps| ps.chips > 0 && !ps.sitting_out)
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+        let positions = blind_positions(&active, r.blinds)
+            .ok_or_else(|| anyhow::anyhow!("not enough players to start a hand"))?;
+        let variant = r.config.variant;
+        // everything a hand deals has to come out of one deck
+        let players = active.iter().filter(|a| **a).count();
+        if players > variant.max_players() {
+            return Err(anyhow::anyhow!("too many players for {}", variant.as_str()));
+        }
+        let client_seeds = r
+            .seats
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| active[*i])
+            .filter_map(|(i, slot)| Some((i, slot.as_ref()?.client_seed.clone()?)))
+            .collect::<Vec<_>>();
+        let seeds = client_seeds
+            .iter()
+            .map(|(_, s)| s.clone())
+            .collect::<Vec<_>>();
+        let deck_source = self.room_deck_source(&r);
+        let mut deck = deck_source.deck(variant, &combined_seed(&r.next_seed, &seeds));
+        let dealt_deck = deck_source.records_deck().then(|| cards_to_json(&deck));
+        let client_seeds = serde_json::json!(
+            client_seeds
+                .iter()
+                .map(|(i, seed)| serde_json::json!({ "seat": i + 1, "seed": seed }))
+                .collect::<Vec<_>>()
+        );
+
         let mut hole_cards = vec![None; r.seats.len()];
-        let mut deck_cards = 0usize;
-        for (i, slot) in r.seats.iter().enumerate() {
-            if slot.is_some() {
-                let card1 = deck.remove(0);
-                let card2 = deck.remove(0);
-                hole_cards[i] = Some((card1, card2));
+        let mut up_cards = vec![Vec::new(); r.seats.len()];
+        for (i, is_active) in active.iter().enumerate() {
+            if *is_active {
+                let cards = deck.drain(0..variant.hole_cards()).collect::<Vec<_>>();
+                if variant.is_stud() {
+                    up_cards[i].push(cards[2]);
+                }
+                hole_cards[i] = Some(cards);
             }
         }
-        let hand_id = Uuid::new_v4();
         let started_at = Utc::now();
-        let players_in_hand = r
+        // the table only moves on to this hand once it is stored
+        let hand_id = create_hand(
+            &self.pool,
+            Some(room_id),
+            Some(started_at),
+            Some(&r.next_seed.hash),
+            Some(client_seeds.clone()),
+            deck_source.name(),
+            dealt_deck,
+        )
+        .await?;
+        r.hands_dealt += 1;
+        let refill = r.config.time_bank_refill_hands;
+        if refill > 0 && r.hands_dealt % refill == 0 {
+            let full = r.config.time_bank;
+            for ps in r.seats.iter_mut().flatten() {
+                ps.time_bank = full;
+            }
+        }
+        r.blinds = Some(positions);
+        r.dealer_index = Some(positions.button);
+        let server_seed = std::mem::replace(&mut r.next_seed, deck_source.server_seed());
+        let seed_hash = server_seed.hash.clone();
+
+        for (i, slot) in r.seats.iter().enumerate() {
+            if let Some(ps) = slot
+                && let Some(cards) = &hole_cards[i]
+            {
+                let hole_j = cards_to_json(cards);
+                let _ = insert_player(
+                    &self.pool,
+                    Some(hand_id),
+                    (i + 1) as i16,
+                    Some(ps.user_id),
+                    Some(hole_j),
+                    Some(ps.chips),
+                    Some(ps.chips),
+                )
+                .await;
+            }
+        }
+
+        let stacks = r
             .seats
             .iter()
-            .map(|slot| slot.is_some())
+            .map(|slot| slot.as_ref().map(|ps| ps.chips).unwrap_or(0))
             .collect::<Vec<_>>();
-        let hand = HandState {
-            id: hand_id,
-            started_at: started_at.timestamp_millis(),
-            pot: 0,
-            board: Vec::new(),
-            hole_cards: hole_cards.clone(),
-            current_turn: Some(Self::first_to_act_index(&r)),
-            round: "pre-flop".to_string(),
-            players_in_hand,
+        let config = r.config.clone();
+        let mut betting = BettingRound::new(
+            stacks,
+            active.clone(),
+            config.big_blind,
+            config.betting_limit,
+            config.raise_cap,
+            0,
+        );
+        let mut posts = Vec::new();
+        if config.ante > 0 {
+            for (i, _) in active.iter().enumerate().filter(|(_, a)| **a) {
+                posts.push((i, "ante", betting.post_dead(i, config.ante)));
+            }
+        }
+        let first_to_act = if variant.is_stud() {
+            // no blinds in stud, so nobody owes one either
+            for ps in r.seats.iter_mut().flatten() {
+                ps.owes_big_blind = false;
+            }
+            let upcards = up_cards
+                .iter()
+                .map(|u| u.first().copied())
+                .collect::<Vec<_>>();
+            let seat = variant
+                .bring_in_seat(&upcards)
+                .ok_or_else(|| anyhow::anyhow!("nobody to bring it in"))?;
+            posts.push((
+                seat,
+                "bring_in",
+                betting.post_bring_in(seat, config.small_blind),
+            ));
+            seat + 1
+        } else {
+            // an empty small blind seat means a dead small blind, nobody posts it
+            if active[positions.small_blind] {
+                let sb = positions.small_blind;
+                posts.push((sb, "post_sb", betting.post_blind(sb, config.small_blind)));
+            }
+            let bb = positions.big_blind;
+            posts.push((bb, "post_bb", betting.post_blind(bb, config.big_blind)));
+            for (i, slot) in r.seats.iter_mut().enumerate() {
+                let Some(ps) = slot else { continue };
+                // a small blind doesn't cover it, the debt stays until a big blind is posted
+                if !ps.owes_big_blind || !active[i] || i == positions.small_blind {
+                    continue;
+                }
+                ps.owes_big_blind = false;
+                if i != positions.big_blind {
+                    posts.push((i, "post_bb", betting.post_blind(i, config.big_blind)));
+                }
+            }
+            bb + 1
         };
-        let _ = create_hand(&self.pool, Some(room_id), Some(started_at)).await;
-        for (i, slot) in r.seats.iter().enumerate() {
-            if let Some(ps) = slot {
-                if let Some((card1, card2)) = &hole_cards[i] {
-                    let hole_j = serde_json::json!([card1.to_string(), card2.to_string()]);
-                    let _ = insert_player(
-                        &self.pool,
-                        Some(hand_id),
-                        (i + 1) as i16,
-                        Some(ps.user_id),
-                        Some(hole_j),
-                        Some(ps.chips),
-                        Some(ps.chips),
-                    )
-                    .await;
+        // seats sitting this hand out miss their blinds and post a big blind to come back
+        if !variant.is_stud() {
+            for (i, slot) in r.seats.iter_mut().enumerate() {
+                if let Some(ps) = slot.as_mut().filter(|ps| ps.sitting_out && !active[i]) {
+                    ps.owes_big_blind = true;
                 }
             }
         }
-        r.active_hand = Some(hand);
-        drop(r);
-        //TODO::spawn_timer
-        //TODO::Implement emit events
-        Ok(hand_id)
-    }
+        betting.start_action(first_to_act);
 
-    fn first_to_act_index(r: &RoomState) -> usize {
-        if let Some(d) = r.dealer_index {
-            let n = r.seats.len();
-            for i in 1..=n {
-                let index = (d + i) % n;
-                if r.seats[index].is_some() {
-                    return index;
-                }
+        let mut pot = 0;
+        for &(seat, action_type, amount) in &posts {
+            pot += amount;
+            if let Some(ps) = r.seats[seat].as_mut() {
+                ps.chips -= amount;
+                let _ = insert_action(
+                    &self.pool,
+                    Some(hand_id),
+                    Some(ps.user_id),
+                    Some(action_type.to_string()),
+                    Some(amount),
+                )
+                .await;
             }
-            0
+        }
+
+        let players = r
+            .seats
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| active[*i])
+            .filter_map(|(i, slot)| Some((i, slot.as_ref()?.user_id)))
+            .collect::<Vec<_>>();
+        let hand = HandState {
+            id: hand_id,
+            pot,
+            board: Vec::new(),
+            hole_cards,
+            up_cards,
+            current_turn: betting.to_act,
+            round: variant.first_street(),
+            deck,
+            players_in_hand: active,
+            players,
+            betting,
+            server_seed,
+            client_seeds,
+        };
+        r.active_hand = Some(hand);
+        // blinds and antes can put everyone all-in before anybody acts
+        let hand_over = Self::progress_hand(&mut r);
+        let table = TableSnapshot::capture(&r);
+        drop(r);
+        let payload = serde_json::json!({ "hand_id": hand_id, "server_seed_hash": seed_hash });
+        let _ = self
+            .emit_events(room_id, "hand_started", payload, Some(&table))
+            .await;
+        if hand_over {
+            drop(entry);
+            self.finish_hand(room_id).await?;
         } else {
-            for (i, s) in r.seats.iter().enumerate() {
-                if s.is_some() {
-                    return i;
-                }
-            }
-            0
+            self.spawn_turn_timer(room_id)?;
         }
+        Ok(hand_id)
     }
 
     pub async fn handle_action(
         &self,
         user_id: Uuid,
         room_id: Uuid,
-        action: serde_json::Value,
+        action: PlayerAction,
+    ) -> anyhow::Result<()> {
+        self.act(user_id, room_id, action, ActionSource::Player)
+            .await
+    }
+
+    async fn act(
+        &self,
+        user_id: Uuid,
+        room_id: Uuid,
+        action: PlayerAction,
+        source: ActionSource,
     ) -> anyhow::Result<()> {
         let entry = self
             .rooms
@@ -258,124 +817,148 @@ impl GameManager {
             .iter()
             .position(|s| s.as_ref().map(|p| p.user_id == user_id).unwrap_or(false))
             .ok_or_else(|| anyhow::anyhow!("player not found"))?;
-        {
-            let hs = r
-                .active_hand
-                .as_ref()
-                .ok_or_else(|| anyhow::anyhow!("no active hand"))?;
-            //let seat = r.seats[seat_index].as_mut();
-            if Some(seat_index) != hs.current_turn {
-                return Err(anyhow::anyhow!("it's not player's turn"));
-            }
-        }
-        let action_type = action
-            .get("type")
-            .and_then(|v| v.as_str())
-            .unwrap_or("unknown")
-            .to_string();
-        let amount = action.get("amount").and_then(|v| v.as_i64()).unwrap_or(0);
-        //let hs = r.active_hand.as_mut().ok_or_else(|| anyhow::anyhow!("no active hand"))?;
-        match action_type.as_str() {
-            "fold" => {
-                let hs = r
-                    .active_hand
-                    .as_mut()
-                    .ok_or_else(|| anyhow::anyhow!("no active hand"))?;
-                hs.players_in_hand[seat_index] = false;
-                let _ = insert_action(
-                    &self.pool,
-                    Some(hs.id),
-                    Some(user_id),
-                    Some(action_type),
-                    Some(amount),
-                )
-                .await;
-                //TODO:: Implement emit event
+
+        let hs = r
+            .active_hand
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("no active hand"))?;
+        // the betting round validates the action and decides how many chips actually move
+        let applied = hs.betting.apply(seat_index, action)?;
+        hs.pot += applied.chips_added;
+        if applied.action == PlayerAction::Fold {
+            hs.players_in_hand[seat_index] = false;
+        }
+        let hand_id = hs.id;
+
+        let mut auto_sat_out = false;
+        if let Some(ps) = r.seats[seat_index].as_mut() {
+            ps.chips -= applied.chips_added;
+            if source == ActionSource::Timeout {
+                ps.timeouts += 1;
+                auto_sat_out = ps.timeouts >= AUTO_SIT_OUT_TIMEOUTS && !ps.sitting_out;
+                ps.sitting_out |= auto_sat_out;
+            } else {
+                ps.timeouts = 0;
             }
-            "bet" | "raise" | "call" => {
-                if let Some(ps) = r.seats[seat_index].as_mut() {
-                    ps.chips -= amount;
-                }
-                let hs = r
-                    .active_hand
-                    .as_mut()
-                    .ok_or_else(|| anyhow::anyhow!("no active hand"))?;
-                hs.pot += amount;
-                let _ = insert_action(
-                    &self.pool,
-                    Some(hs.id),
-                    Some(user_id),
-                    Some(action_type),
-                    Some(amount),
-                )
+        }
+        let _ = insert_action(
+            &self.pool,
+            Some(hand_id),
+            Some(user_id),
+            Some(applied.name().to_string()),
+            Some(applied.chips_added),
+        )
+        .await;
+        let street = r.active_hand.as_ref().map(|hs| hs.round);
+        let hand_over = Self::progress_hand(&mut r);
+        let dealt = r.active_hand.as_ref().map(|hs| hs.round) != street;
+        let table = TableSnapshot::capture(&r);
+        drop(r);
+        let payload = serde_json::json!({
+            "seat": seat_index + 1,
+            "action": applied.name(),
+            "amount": applied.chips_added,
+            "timed_out": source == ActionSource::Timeout,
+            "pre_action": source == ActionSource::PreAction,
+        });
+        let _ = self
+            .emit_events(room_id, "player_acted", payload, Some(&table))
+            .await;
+        if auto_sat_out {
+            let payload = serde_json::json!({
+                "seat": seat_index + 1,
+                "sitting_out": true,
+                "auto": true,
+            });
+            let _ = self
+                .emit_events(room_id, "player_sitting_out", payload, Some(&table))
                 .await;
-                //TODO:: Implement emit event
-            }
-            "check" => {
-                let hs = r
-                    .active_hand
-                    .as_ref()
-                    .ok_or_else(|| anyhow::anyhow!("no active hand"))?;
-                let _ = insert_action(
-                    &self.pool,
-                    Some(hs.id),
-                    Some(user_id),
-                    Some(action_type),
-                    Some(amount),
-                )
+        }
+        if dealt && !hand_over {
+            let payload = serde_json::json!({ "hand_id": hand_id });
+            let _ = self
+                .emit_events(room_id, "street_dealt", payload, Some(&table))
                 .await;
-                //TODO:: Implement emit event
-            }
-            "allin" => {
-                if let Some(ps) = r.seats[seat_index].as_mut() {
-                    let amt = ps.chips;
-                    ps.chips = 0;
-                    let hs = r
-                        .active_hand
-                        .as_mut()
-                        .ok_or_else(|| anyhow::anyhow!("no active hand"))?;
-                    hs.pot += amt;
-                    let _ = insert_action(
-                        &self.pool,
-                        Some(hs.id),
-                        Some(user_id),
-                        Some(action_type),
-                        Some(amount),
-                    )
-                    .await;
-                    //TODO:: Implement emit event
-                }
+        }
+        if hand_over {
+            drop(entry);
+            self.finish_hand(room_id).await?;
+        } else {
+            self.spawn_turn_timer(room_id)?;
+        }
+        Ok(())
+    }
+
+    // deals the following streets once betting closes (all of them if everyone is all-in)
+    // returns true when the hand is over and has to go to finish_hand
+    fn progress_hand(r: &mut RoomState) -> bool {
+        let left_of_button = r.dealer_index.map(|d| d + 1).unwrap_or(0);
+        let variant = r.config.variant;
+        let config = r.config.clone();
+        let Some(hs) = r.active_hand.as_mut() else {
+            return false;
+        };
+        while hs.betting.is_complete() {
+            if hs.betting.players_in_hand() <= 1 {
+                return true;
             }
-            other => {
-                return Err(anyhow::anyhow!("Unsupported Action {}", other));
+            hs.round = hs.round.next();
+            match hs.round {
+                Street::Flop => {
+                    burn_card(&mut hs.deck);
+                    hs.board.extend(deal_flop(&mut hs.deck));
+                }
+                Street::Turn => {
+                    burn_card(&mut hs.deck);
+                    hs.board.extend(deal_turn(&mut hs.deck));
+                }
+                Street::River => {
+                    burn_card(&mut hs.deck);
+                    hs.board.extend(deal_river(&mut hs.deck));
+                }
+                Street::Fourth | Street::Fifth | Street::Sixth | Street::Seventh => {
+                    Self::deal_stud_street(hs);
+                }
+                Street::PreFlop | Street::Third | Street::Showdown => return true,
             }
+            // stud action starts with the best hand showing, flop games left of the button
+            let first = if variant.is_stud() {
+                variant
+                    .best_showing(&hs.up_cards, &hs.players_in_hand)
+                    .unwrap_or(left_of_button)
+            } else {
+                left_of_button
+            };
+            hs.betting.new_street(first, config.bet_size(hs.round));
         }
-        let next_turn = {
-            let hs = r
-                .active_hand
-                .as_mut()
-                .ok_or_else(|| anyhow::anyhow!("no active hand"))?;
-            let next = Self::next_live_player(&hs.players_in_hand, seat_index);
-            hs.current_turn = next;
-        };
+        hs.current_turn = hs.betting.to_act;
+        false
+    }
 
-        //TODO: spawn timer
-        if let Some(entry2) = self.rooms.get(&room_id) {
-            let r2 = entry2.value().read().await;
-            let alive = r2
-                .active_hand
-                .as_ref()
-                .unwrap()
-                .players_in_hand
-                .iter()
-                .filter(|&&p| p)
-                .count();
-            if alive <= 1 {
-                drop(r2);
-                //TODO:: finish hand
+    // one card to every player still in the hand, face down on seventh street.
+    // burns only while there are cards to spare, and when the deck can't go round on
+    // seventh street a single card is dealt face up on the board for everybody
+    fn deal_stud_street(hs: &mut HandState) {
+        let face_up = hs.round != Street::Seventh;
+        let live = (0..hs.players_in_hand.len())
+            .filter(|&i| hs.players_in_hand[i])
+            .collect::<Vec<_>>();
+        if hs.deck.len() > live.len() {
+            burn_card(&mut hs.deck);
+        }
+        if hs.deck.len() < live.len() {
+            hs.board.extend(deal_river(&mut hs.deck));
+            return;
+        }
+        for seat in live {
+            let card = hs.deck.remove(0);
+            if let Some(cards) = hs.hole_cards[seat].as_mut() {
+                cards.push(card);
+            }
+            if face_up {
+                hs.up_cards[seat].push(card);
             }
         }
-        Ok(())
     }
 
     pub async fn finish_hand(&self, room_id: Uuid) -> anyhow::Result<()> {
@@ -388,157 +971,613 @@ impl GameManager {
             .active_hand
             .take()
             .ok_or_else(|| anyhow::anyhow!("no active hand"))?;
-        let mut best_rank: Option<(usize, HandRank)> = None;
-        for (i, alive) in hs.players_in_hand.iter().enumerate() {
-            if !*alive {
-                continue;
-            }
-            if let Some((c1, c2)) = &hs.hole_cards[i] {
-                let mut cards = hs.board.clone();
-                cards.push(*c1);
-                cards.push(*c2);
-                let rank = evaluate_best_of_seven(&cards);
-                match &best_rank {
-                    None => best_rank = Some((i, rank)),
-                    Some((_, rprev)) => {
-                        if rank > *rprev {
-                            best_rank = Some((i, rank))
-                        }
+        if let Some(tok) = r.turn_task.take() {
+            tok.cancel();
+        }
+        for ps in r.seats.iter_mut().flatten() {
+            ps.pre_action = None;
+        }
+        let variant = r.config.variant;
+        // hands that end before showdown go to the last player without looking at cards
+        let showdown = hs.round == Street::Showdown;
+        let ranks = hs
+            .players_in_hand
+            .iter()
+            .enumerate()
+            .map(|(i, alive)| {
+                let hole = hs.hole_cards[i].as_ref()?;
+                if !*alive || !showdown {
+                    return None;
+                }
+                Some(variant.evaluate(hole, &hs.board))
+            })
+            .collect::<Vec<Option<HandRank>>>();
+        let lows = hs
+            .players_in_hand
+            .iter()
+            .enumerate()
+            .map(|(i, alive)| {
+                let hole = hs.hole_cards[i].as_ref()?;
+                if !*alive || !showdown {
+                    return None;
+                }
+                variant.evaluate_low(hole, &hs.board)
+            })
+            .collect::<Vec<Option<LowRank>>>();
+
+        let mut pots = build_pots(&hs.betting.invested, &hs.players_in_hand);
+        let dealt = hs.hole_cards.iter().filter(|h| h.is_some()).count();
+        let saw_flop = hs.round != variant.first_street();
+        let raked = r
+            .config
+            .rake
+            .take(&mut pots, &hs.betting.invested, dealt, saw_flop);
+        let button = r.dealer_index.unwrap_or(r.seats.len() - 1);
+        let awards = if variant.is_lowball() {
+            award_pots(&pots, &lows, button)
+        } else if variant.is_hi_lo() {
+            award_pots_hi_lo(&pots, &ranks, &lows, button)
+        } else {
+            award_pots(&pots, &ranks, button)
+        };
+
+        let dealt_in = |seat: usize| {
+            hs.players
+                .iter()
+                .find(|&&(s, _)| s == seat)
+                .map(|&(_, user_id)| user_id)
+        };
+        // chips won less chips put in, the ledger only moves the difference
+        let mut nets = hs
+            .betting
+            .invested
+            .iter()
+            .map(|&c| -c)
+            .collect::<Vec<i64>>();
+        // the first winner of a pot is charged its rake, and so credited it with the pot
+        let mut rake_paid = Vec::new();
+        // stacks are only credited once the ledger took the result
+        let mut credits = Vec::new();
+        let mut pots_json = Vec::new();
+        for (award, &rake) in awards.iter().zip(&raked) {
+            for &(seat, amount) in award.winners.iter().chain(&award.low_winners) {
+                if let Some(net) = nets.get_mut(seat) {
+                    *net += amount;
+                }
+            }
+            if let Some(&(seat, _)) = award.winners.first().filter(|_| rake > 0) {
+                nets[seat] += rake;
+                rake_paid.push((seat, rake));
+            }
+            let mut pay = |shares: &[(usize, i64)]| {
+                let mut out = Vec::new();
+                for &(seat, amount) in shares {
+                    let Some(user_id) = dealt_in(seat) else {
+                        continue;
+                    };
+                    credits.push((seat, user_id, amount));
+                    out.push(serde_json::json!({
+                        "seat": seat + 1,
+                        "user_id": user_id,
+                        "amount": amount,
+                    }));
+                }
+                out
+            };
+            let winners_json = pay(&award.winners);
+            let low_winners_json = pay(&award.low_winners);
+            let mut pot_json = serde_json::json!({
+                "amount": award.amount,
+                "rake": rake,
+                "eligible_seats": award.eligible.iter().map(|s| s + 1).collect::<Vec<_>>(),
+                "winners": winners_json,
+            });
+            // split games: winners took the high half, or all of it when nobody made a low
+            if variant.is_hi_lo() {
+                pot_json["low_winners"] = serde_json::json!(low_winners_json);
+            }
+            pots_json.push(pot_json);
+        }
+
+        // the hand is over either way, so failures are logged and the first one is returned once
+        // the table has moved on
+        let mut failed = None;
+        let mut log_failure = |what: &str, result: anyhow::Result<()>| {
+            if let Err(e) = result {
+                tracing::error!("could not {} for hand {}: {}", what, hs.id, e);
+                failed.get_or_insert(e);
+            }
+        };
+        // whoever sits in a seat now may have joined after the deal, or its player left after
+        // folding, so the hand settles with the players it was dealt to
+        let nets = hs
+            .players
+            .iter()
+            .map(|&(seat, user_id)| (user_id, nets[seat]))
+            .collect::<Vec<_>>();
+        let rake_paid = rake_paid
+            .into_iter()
+            .filter_map(|(seat, rake)| dealt_in(seat).map(|user_id| (user_id, rake)))
+            .collect::<Vec<_>>();
+        // the ledger first, so a stack never holds chips its escrow doesn't
+        let posted = post_hand_result(&self.pool, room_id, hs.id, &nets, &rake_paid).await;
+        let settled = posted.is_ok();
+        log_failure("post the hand result", posted);
+        let seated = |ps: &PlayerSlot, user_id: Uuid| ps.user_id == user_id;
+        if settled {
+            for &(seat, user_id, amount) in &credits {
+                if let Some(ps) = r.seats[seat].as_mut().filter(|ps| seated(ps, user_id)) {
+                    ps.chips += amount;
+                }
+            }
+        } else {
+            // nothing moved in the ledger, so the hand is called off and everyone gets back what
+            // they put in. a player who left since still has it in escrow and is paid out
+            for &(seat, user_id) in &hs.players {
+                let invested = hs.betting.invested[seat];
+                match r.seats[seat].as_mut().filter(|ps| seated(ps, user_id)) {
+                    Some(ps) => ps.chips += invested,
+                    None if invested > 0 => {
+                        let refunded = post_cash_out(&self.pool, user_id, room_id, invested).await;
+                        log_failure("refund a player who left", refunded);
                     }
+                    None => {}
                 }
             }
         }
 
-        if let Some((winner_index, rank)) = best_rank {
-            if let Some(Some(ps)) = r.seats.get_mut(winner_index) {
-                ps.chips += hs.pot;
-                let winner_id = ps.user_id;
-                let time = Utc::now();
-                let _ = finish_hand(
-                    &self.pool,
-                    hs.id,
-                    Some(time),
-                    hs.pot,
-                    None,
-                    Some(winner_id),
-                    Some(serde_json::json!({"pot": hs.pot})),
-                )
-                .await;
-                //TODO: emit event
-            } else {
-                let time = Utc::now();
-                let _ = finish_hand(
-                    &self.pool,
-                    hs.id,
-                    Some(time),
-                    hs.pot,
-                    None,
-                    None,
-                    Some(serde_json::json!({"pot": hs.pot})),
-                )
-                .await;
-                //TODO: emit event
+        // winner_user_id keeps pointing at whoever took (the first share of) the main pot
+        let winner_id = awards
+            .first()
+            .and_then(|a| a.winners.first())
+            .and_then(|&(seat, _)| dealt_in(seat))
+            .filter(|_| settled);
+        let board_json = cards_to_json(&hs.board);
+        let result_json = if settled {
+            serde_json::json!({
+                "variant": variant.as_str(),
+                "pot": hs.pot,
+                "rake": raked.iter().sum::<i64>(),
+                "pots": pots_json,
+            })
+        } else {
+            serde_json::json!({
+                "variant": variant.as_str(),
+                "pot": hs.pot,
+                "voided": true,
+            })
+        };
+        let _ = finish_hand(
+            &self.pool,
+            hs.id,
+            Some(Utc::now()),
+            hs.pot,
+            Some(board_json),
+            winner_id,
+            Some(result_json.clone()),
+        )
+        .await;
+        let server_seed = hs.server_seed.reveal();
+        let revealed = reveal_hand_seed(&self.pool, hs.id, &server_seed).await;
+        log_failure("reveal the server seed", revealed);
+        for (i, slot) in r.seats.iter().enumerate() {
+            if let Some(ps) = slot {
+                let _ = update_chips(&self.pool, room_id, ps.chips, (i + 1) as i16).await;
+            }
+        }
+        let mut table = TableSnapshot::of_hand(&r, Some(&hs));
+        let mut left = Vec::new();
+        for i in 0..r.seats.len() {
+            if let Some(ps) = r.seats[i].take_if(|ps| ps.leaving) {
+                let cashed_out = self.cash_out(room_id, i, &ps).await;
+                if cashed_out.is_err() {
+                    // still leaving, tried again when the next hand ends
+                    r.seats[i] = Some(ps);
+                    log_failure("cash out a leaving player", cashed_out);
+                    continue;
+                }
+                left.push(
+                    serde_json::json!({ "seat": i + 1, "user_id": ps.user_id, "chips": ps.chips }),
+                );
             }
         }
+        if showdown {
+            table.reveal_showdown();
+        }
+        let payload = serde_json::json!({
+            "hand_id": hs.id,
+            "result": result_json,
+            "server_seed": server_seed,
+            "server_seed_hash": hs.server_seed.hash,
+            "client_seeds": hs.client_seeds,
+            "next_seed_hash": r.next_seed.hash,
+        });
+        drop(r);
+        let _ = self
+            .emit_events(room_id, "hand_finished", payload, Some(&table))
+            .await;
+        if !left.is_empty() {
+            let table = TableSnapshot::capture(&*entry.value().read().await);
+            for payload in left {
+                let _ = self
+                    .emit_events(room_id, "player_left", payload, Some(&table))
+                    .await;
+            }
+        }
+        self.publish_lobby(room_id);
+        self.schedule_hand(room_id);
+        failed.map_or(Ok(()), Err)
+    }
+
+    // used for the next hand dealt, returns the server commitment it will be combined with
+    pub async fn set_client_seed(
+        &self,
+        user_id: Uuid,
+        room_id: Uuid,
+        seed: &str,
+    ) -> anyhow::Result<String> {
+        validate_client_seed(seed)?;
+        let entry = self
+            .rooms
+            .get(&room_id)
+            .ok_or_else(|| anyhow::anyhow!("room not found"))?;
+        let mut r = entry.value().write().await;
+        let ps = r
+            .seats
+            .iter_mut()
+            .flatten()
+            .find(|p| p.user_id == user_id)
+            .ok_or_else(|| anyhow::anyhow!("player not found"))?;
+        ps.client_seed = Some(seed.to_string());
+        Ok(r.next_seed.hash.clone())
+    }
+
+    // takes effect from the next hand, a hand already dealt is played out.
+    // coming back after missing a hand's blinds costs a big blind, like joining does
+    pub async fn set_sitting_out(
+        &self,
+        user_id: Uuid,
+        room_id: Uuid,
+        sitting_out: bool,
+    ) -> anyhow::Result<()> {
+        let entry = self
+            .rooms
+            .get(&room_id)
+            .ok_or_else(|| anyhow::anyhow!("room not found"))?;
+        let mut r = entry.value().write().await;
+        let index = r
+            .seats
+            .iter()
+            .position(|s| s.as_ref().is_some_and(|p| p.user_id == user_id))
+            .ok_or_else(|| anyhow::anyhow!("player not found"))?;
+        let Some(ps) = r.seats[index].as_mut() else {
+            return Ok(());
+        };
+        if ps.sitting_out == sitting_out {
+            return Ok(());
+        }
+        ps.sitting_out = sitting_out;
+        ps.timeouts = 0;
+        let table = TableSnapshot::capture(&r);
+        drop(r);
+        let payload = serde_json::json!({
+            "seat": index + 1,
+            "sitting_out": sitting_out,
+            "auto": false,
+        });
+        let _ = self
+            .emit_events(room_id, "player_sitting_out", payload, Some(&table))
+            .await;
+        if !sitting_out {
+            self.schedule_hand(room_id);
+        }
         Ok(())
     }
 
-    fn next_live_player(player_in_hand: &Vec<bool>, from: usize) -> Option<usize> {
-        let n = player_in_hand.len();
-        if n == 0 {
-            return None;
+    // None clears it. only for a hand the player is still in and before it is their turn
+    pub async fn set_pre_action(
+        &self,
+        user_id: Uuid,
+        room_id: Uuid,
+        pre_action: Option<PreAction>,
+    ) -> anyhow::Result<()> {
+        let entry = self
+            .rooms
+            .get(&room_id)
+            .ok_or_else(|| anyhow::anyhow!("room not found"))?;
+        let mut r = entry.value().write().await;
+        let index = r
+            .seats
+            .iter()
+            .position(|s| s.as_ref().is_some_and(|p| p.user_id == user_id))
+            .ok_or_else(|| anyhow::anyhow!("player not found"))?;
+        if pre_action.is_some() {
+            let hs = r
+                .active_hand
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("no active hand"))?;
+            if !hs.players_in_hand[index] {
+                return Err(anyhow::anyhow!("not in this hand"));
+            }
+            if hs.current_turn == Some(index) {
+                return Err(anyhow::anyhow!("it is your turn, act instead"));
+            }
+        }
+        if let Some(ps) = r.seats[index].as_mut() {
+            ps.pre_action = pre_action;
+        }
+        Ok(())
+    }
+
+    // a dropped socket only marks the seat, the player keeps it and the turn timer still runs
+    pub async fn set_connected(
+        &self,
+        user_id: Uuid,
+        room_id: Uuid,
+        connected: bool,
+    ) -> anyhow::Result<()> {
+        let entry = self
+            .rooms
+            .get(&room_id)
+            .ok_or_else(|| anyhow::anyhow!("room not found"))?;
+        let mut r = entry.value().write().await;
+        let Some(index) = r
+            .seats
+            .iter()
+            .position(|s| s.as_ref().is_some_and(|p| p.user_id == user_id))
+        else {
+            return Ok(()); // spectators have no seat to mark
+        };
+        if let Some(ps) = r.seats[index].as_mut() {
+            if ps.connected == connected {
+                return Ok(());
+            }
+            ps.connected = connected;
         }
-        for i in 1..=n {
-            let index = (from + i) % n;
-            if player_in_hand[index] {
-                return Some(index);
+        set_connected(&self.pool, room_id, (index + 1) as i16, connected).await?;
+        let table = TableSnapshot::capture(&r);
+        drop(r);
+        let payload = serde_json::json!({
+            "seat": index + 1,
+            "user_id": user_id,
+            "connected": connected,
+        });
+        let _ = self
+            .emit_events(room_id, "player_connection", payload, Some(&table))
+            .await;
+        Ok(())
+    }
+
+    // registers a reconnecting socket. the events it missed after last_seq are queued on it
+    // and then "resumed", when they are no longer all buffered (or don't fit its queue) it gets
+    // a snapshot instead. both go out before any newer event
+    pub async fn resume(
+        &self,
+        client: ClientInfo,
+        room_id: Uuid,
+        last_seq: Option<u64>,
+    ) -> anyhow::Result<()> {
+        let entry = self
+            .rooms
+            .get(&room_id)
+            .ok_or_else(|| anyhow::anyhow!("room not found"))?
+            .value()
+            .clone();
+        let r = entry.read().await;
+        let log = self.event_log.entry(room_id).or_default();
+        let seq = log.last_seq();
+        let missed = last_seq
+            .and_then(|s| log.since(s))
+            .filter(|events| events.len() < client.tx.capacity());
+        let reply = match missed {
+            Some(events) => {
+                for ev in &events {
+                    let view = ServerEvent::Game(ev.view_for(Some(client.user_id)));
+                    if let Some(out) = view.to_outgoing() {
+                        let _ = client.tx.try_send(out);
+                    }
+                }
+                ServerEvent::Resumed {
+                    room_id,
+                    seq,
+                    replayed: events.len(),
+                }
             }
+            None => ServerEvent::Snapshot {
+                room_id,
+                seq,
+                table: TableSnapshot::capture(&r).view_for(Some(client.user_id)),
+            },
+        };
+        if let Some(out) = reply.to_outgoing() {
+            let _ = client.tx.try_send(out);
         }
-        None
+        let user_id = client.user_id;
+        let mut clients = self.client_registry.entry(room_id).or_default();
+        clients.retain(|ci| ci.conn_id != client.conn_id);
+        clients.push(client);
+        drop(clients);
+        drop(log);
+        drop(r);
+        self.set_connected(user_id, room_id, true).await
     }
 
+    // the player to act gets the room's action clock and then their time bank. whatever the
+    // turn doesn't use of the bank is kept. once both run out the player checks if they can
+    // and folds otherwise
     pub fn spawn_turn_timer(&self, room_id: Uuid) -> anyhow::Result<()> {
         let gm = self.clone();
-
         tokio::spawn(async move {
-            if let Some(entry) = gm.rooms.get(&room_id) {
-                {
-                    let mut r = entry.value().write().await;
-                    if let Some(tok) = r.turn_task.take() {
-                        tok.cancel();
-                    }
-                    let token = CancellationToken::new();
-                    r.turn_task = Some(token.clone());
-                    let room_clone = room_id.clone();
-                    let current_turn = r
-                        .active_hand
-                        .as_ref()
-                        .and_then(|h| h.current_turn)
-                        .unwrap_or(usize::MAX);
-                    let hand_id = r.active_hand.as_ref().map(|h| h.id);
-                    let sleep = tokio::time::sleep(Duration::from_secs(30));
-                    tokio::select! {
-                        _ = token.cancelled() => {return;}
-                        _ = sleep => {}
-                    }
-                    if let Some(e) = gm.rooms.get(&room_clone) {
-                        let mut r2 = e.value().write().await;
-                        let user_id = {
-                            if current_turn >= r2.seats.len() {
-                                return;
-                            }
-                            let ps = match r2.seats[current_turn].as_ref() {
-                                Some(ps) => ps,
-                                None => return,
-                            };
-                            ps.user_id
-                        };
-                        if let Some(hs) = r2.active_hand.as_mut() {
-                            if hs.current_turn == Some(current_turn) {
-                                hs.players_in_hand[current_turn] = false;
-                                let _ = insert_action(
-                                    &gm.pool,
-                                    hand_id,
-                                    Some(user_id),
-                                    Some(String::from("timeout")),
-                                    Some(0),
-                                )
-                                .await;
-                                //TODO : emit event
-                                let next =
-                                    Self::next_live_player(&hs.players_in_hand, current_turn);
-                                hs.current_turn = next;
-                                drop(r2);
-                                let gm_clone = gm.clone();
-                                let room_clone2 = room_clone.clone();
-                                tokio::spawn(async move {
-                                    let _ = gm_clone.spawn_turn_timer(room_clone2);
-                                });
-                            }
-                        }
-                    }
-                }
+            let _ = gm.run_turn_timer(room_id).await;
+        });
+        Ok(())
+    }
+
+    async fn run_turn_timer(&self, room_id: Uuid) -> anyhow::Result<()> {
+        let entry = self
+            .rooms
+            .get(&room_id)
+            .ok_or_else(|| anyhow::anyhow!("room not found"))?
+            .value()
+            .clone();
+        let (token, hand_id, seat, user_id, clock, bank) = {
+            let mut r = entry.write().await;
+            if let Some(tok) = r.turn_task.take() {
+                tok.cancel();
+            }
+            let Some(hs) = r.active_hand.as_ref() else {
+                return Ok(());
+            };
+            let (hand_id, Some(seat)) = (hs.id, hs.current_turn) else {
+                return Ok(());
             };
+            let legal = hs.betting.legal_actions(seat);
+            let Some(ps) = r.seats[seat].as_mut() else {
+                return Ok(());
+            };
+            let (user_id, bank) = (ps.user_id, ps.time_bank);
+            let pre = if ps.leaving {
+                Some(PreAction::CheckFold)
+            } else {
+                ps.pre_action.take()
+            };
+            if let Some(pre) = pre {
+                let action = pre.action(&legal);
+                drop(r);
+                return self
+                    .act(user_id, room_id, action, ActionSource::PreAction)
+                    .await;
+            }
+            let token = CancellationToken::new();
+            r.turn_task = Some(token.clone());
+            (token, hand_id, seat, user_id, r.config.action_clock, bank)
+        };
+
+        let payload = serde_json::json!({
+            "hand_id": hand_id,
+            "seat": seat + 1,
+            "deadline": deadline(clock),
+            "time_bank_ms": bank.as_millis() as u64,
         });
+        let _ = self
+            .emit_events(room_id, "timer_started", payload, None)
+            .await;
+        if !expired(&token, clock).await {
+            return Ok(());
+        }
 
-        Ok(())
+        if !bank.is_zero() {
+            let payload = serde_json::json!({
+                "hand_id": hand_id,
+                "seat": seat + 1,
+                "deadline": deadline(bank),
+            });
+            let _ = self
+                .emit_events(room_id, "time_bank_activated", payload, None)
+                .await;
+            let started = Instant::now();
+            let timed_out = expired(&token, bank).await;
+            let used = started.elapsed().min(bank);
+            let mut r = entry.write().await;
+            if let Some(ps) = r.seats[seat].as_mut().filter(|ps| ps.user_id == user_id) {
+                ps.time_bank = ps.time_bank.saturating_sub(used);
+            }
+            if !timed_out {
+                return Ok(());
+            }
+        }
+
+        let action = {
+            let r = entry.read().await;
+            let still_to_act = r
+                .active_hand
+                .as_ref()
+                .filter(|hs| hs.id == hand_id && hs.current_turn == Some(seat));
+            let Some(hs) = still_to_act.filter(|_| !token.is_cancelled()) else {
+                return Ok(());
+            };
+            if hs.betting.legal_actions(seat).contains(&LegalAction::Check) {
+                PlayerAction::Check
+            } else {
+                PlayerAction::Fold
+            }
+        };
+        self.act(user_id, room_id, action, ActionSource::Timeout)
+            .await
+    }
+
+    // (seated, max players) of a room with state in memory
+    pub async fn occupancy(&self, room_id: Uuid) -> Option<(usize, usize)> {
+        let room = self.rooms.get(&room_id)?.value().clone();
+        let r = room.read().await;
+        Some((r.seats.iter().flatten().count(), r.max_players))
+    }
+
+    // sends the room's lobby row, or its removal once closed, to every lobby subscriber.
+    // runs in the background so game events don't wait on the stats queries
+    pub fn publish_lobby(&self, room_id: Uuid) {
+        if self.lobby_subscribers.is_empty() {
+            return;
+        }
+        let gm = self.clone();
+        tokio::spawn(async move {
+            let room = find_by_id_rooms(&gm.pool, room_id)
+                .await
+                .ok()
+                .flatten()
+                .filter(|room| room.room_status != ROOM_CLOSED);
+            let event = match room {
+                Some(room) => match lobby_tables(&gm, vec![room]).await {
+                    Ok(mut tables) if !tables.is_empty() => ServerEvent::LobbyTable {
+                        table: tables.remove(0),
+                    },
+                    _ => return,
+                },
+                None => ServerEvent::LobbyRemoved { room_id },
+            };
+            let Some(out) = event.to_outgoing() else {
+                return;
+            };
+            gm.lobby_subscribers.retain(|_, tx| !tx.is_closed());
+            for tx in gm.lobby_subscribers.iter() {
+                let _ = tx.try_send(out.clone());
+            }
+        });
     }
 
-    pub async fn emit_events(&self, room_id: Uuid , event_type: &str , payload: serde_json::Value) -> anyhow::Result<()>{
-        let ev = OutgoingEvent{
-            event_type: event_type.to_string(),
-            room_id,
-            payload: payload.clone(),
-            emitted_at: Utc::now().timestamp_millis(),
+    // events about the table carry it as "table", projected for every recipient so hole cards
+    // only reach their owner until they are shown down
+    pub async fn emit_events(
+        &self,
+        room_id: Uuid,
+        event_type: &str,
+        payload: serde_json::Value,
+        table: Option<&TableSnapshot>,
+    ) -> anyhow::Result<()> {
+        // the log entry stays locked until every client has the event, so seq order is send order
+        let mut log = self.event_log.entry(room_id).or_default();
+        let logged = LoggedEvent {
+            event: OutgoingEvent {
+                seq: log.next_seq(),
+                event_type: event_type.to_string(),
+                room_id,
+                payload,
+                emitted_at: Utc::now().timestamp_millis(),
+            },
+            table: table.cloned(),
         };
-        //broadcast locally
-        if let Some(clients) = self.client_registry.get(&room_id){
-            if let Ok(s) =  serde_json::to_string(&ev) {
-                for ci in clients.iter(){
-                    let _ = ci.tx.try_send(Outgoing::Text(s.clone()));
+        //broadcast locally, each client only sees its own hole cards
+        if let Some(clients) = self.client_registry.get(&room_id) {
+            for ci in clients.iter() {
+                let view = ServerEvent::Game(logged.view_for(Some(ci.user_id)));
+                if let Some(out) = view.to_outgoing() {
+                    let _ = ci.tx.try_send(out);
                 }
             }
         }
+        let ev = logged.view_for(None);
+        log.push(logged);
+        drop(log);
 
         //append to redis stream "rooms:events"
         let stream_key = "rooms:events";
@@ -551,8 +1590,9 @@ impl GameManager {
         // ];
         let room_id_str = room_id.to_string();
         let type_str =  event_type.to_string();
-        let payload =  serde_json::to_string(&payload)?;
+        let payload =  serde_json::to_string(&ev.payload)?;
         let emitted_at = ev.emitted_at.to_string();
+        let seq = ev.seq.to_string();
 
         //XADD
         let _i: String = redis::cmd("XADD")
@@ -562,9 +1602,23 @@ impl GameManager {
         .arg("type").arg(type_str)
         .arg("payload").arg(payload)
         .arg("emitted_at").arg(emitted_at)
+        .arg("seq").arg(seq)
         .query_async(&mut connection).await
         .map_err(|e| anyhow::anyhow!("redis XADD error: {}", e))?;
 
         Ok(())
     }
 }
+
+// unix millis when a countdown of time started now ends
+fn deadline(time: Duration) -> i64 {
+    Utc::now().timestamp_millis() + time.as_millis() as i64
+}
+
+// true when the time ran out, false when the turn ended first
+async fn expired(token: &CancellationToken, time: Duration) -> bool {
+    tokio::select! {
+        _ = token.cancelled() => false,
+        _ = tokio::time::sleep(time) => true,
+    }
+}
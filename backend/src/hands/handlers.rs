@@ -0,0 +1,79 @@
+use actix_web::{HttpResponse, web};
+use database::models::{find_by_id_hands, find_by_id_rooms, list_by_hand_players};
+use uuid::Uuid;
+
+use crate::errors::ServiceError;
+use crate::fairness::{ServerSeed, combined_seed, shuffled_deck};
+use crate::notation::{cards_from_json, cards_to_json};
+use crate::poker_engine::GameVariant;
+use crate::state::AppState;
+
+// rebuilds the deck of a finished hand from its revealed seeds, anyone can call it
+pub async fn verify_hand(
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let hand_id = path.into_inner();
+    let hand = find_by_id_hands(&state.pool, hand_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("hand {}", hand_id)))?;
+    let Some(revealed) = hand.server_seed.as_deref() else {
+        return Err(ServiceError::Conflict(
+            "the server seed is revealed once the hand is finished".to_string(),
+        ));
+    };
+    let server_seed =
+        ServerSeed::from_hex(revealed).map_err(|_| ServiceError::InternalServerError)?;
+    let client_seeds = hand
+        .client_seeds
+        .as_ref()
+        .and_then(|v| v.as_array())
+        .map(|seeds| {
+            seeds
+                .iter()
+                .filter_map(|s| s.get("seed")?.as_str().map(str::to_string))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let variant = match hand.room_id {
+        Some(room_id) => find_by_id_rooms(&state.pool, room_id)
+            .await?
+            .and_then(|room| GameVariant::from_name(&room.variant))
+            .unwrap_or_default(),
+        None => GameVariant::default(),
+    };
+    let seed = combined_seed(&server_seed, &client_seeds);
+    // stacked decks aren't shuffled, the hand kept the deck itself
+    let deck = match hand.dealt_deck.as_ref() {
+        Some(dealt) => cards_from_json(dealt).map_err(|_| ServiceError::InternalServerError)?,
+        None => shuffled_deck(variant, &seed),
+    };
+
+    // hole cards come off the top of the deck in seat order
+    let players = list_by_hand_players(&state.pool, Some(hand_id)).await?;
+    let mut next = 0;
+    let mut dealt_matches = true;
+    for player in &players {
+        let dealt = player
+            .hole_cards
+            .as_ref()
+            .and_then(|cards| cards_from_json(cards).ok())
+            .unwrap_or_default();
+        dealt_matches &= deck.get(next..next + dealt.len()) == Some(dealt.as_slice());
+        next += dealt.len();
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "hand_id": hand_id,
+        "variant": variant.as_str(),
+        "deck_source": hand.deck_source,
+        "server_seed": revealed,
+        "server_seed_hash": hand.server_seed_hash,
+        "hash_matches": hand.server_seed_hash.as_deref() == Some(server_seed.hash.as_str()),
+        "client_seeds": hand.client_seeds,
+        "combined_seed": hex::encode(seed),
+        "deck": cards_to_json(&deck),
+        "dealt_matches": dealt_matches,
+    })))
+}
@@ -0,0 +1,9 @@
+pub mod handlers;
+
+use actix_web::web;
+
+use crate::hands::handlers::verify_hand;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/{hand_id}/verify").route(web::get().to(verify_hand)));
+}
@@ -0,0 +1,18 @@
+use actix_web::{HttpResponse, web};
+
+use crate::errors::ServiceError;
+use crate::game_manager::GameManager;
+use crate::lobby::listing::{LobbyFilter, open_tables};
+
+// ?variant=holdem&min_big_blind=2&max_big_blind=10&has_open_seat=true&sort=avg_pot&ascending=false
+pub async fn get_lobby(
+    gm: web::Data<GameManager>,
+    query: web::Query<LobbyFilter>,
+) -> Result<HttpResponse, ServiceError> {
+    let tables = open_tables(&gm).await?;
+    let tables = query.apply(tables);
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "tables": tables,
+        "count": tables.len(),
+    })))
+}
@@ -0,0 +1,128 @@
+use chrono::{Duration, Utc};
+use database::models::{Rooms, hand_stats_by_room, list_by_status};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::game_manager::{DEFAULT_MAX_PLAYERS, GameManager, MAX_SEATS};
+
+pub const OPEN_STATUSES: [&str; 2] = ["waiting", "playing"];
+
+// one row of the lobby. average pot and hands per hour cover the hands of the last hour
+#[derive(Debug, Clone, Serialize)]
+pub struct LobbyTable {
+    pub room_id: Uuid,
+    pub room_name: Option<String>,
+    pub room_status: String,
+    pub variant: String,
+    pub betting_limit: Option<String>,
+    pub small_blind: i64,
+    pub big_blind: i64,
+    pub ante: i64,
+    pub min_buy_in: i64,
+    pub max_buy_in: i64,
+    pub rake_bps: i16,
+    pub seated: usize,
+    pub max_players: usize,
+    pub avg_pot: i64,
+    pub hands_per_hour: i64,
+    #[serde(skip)]
+    created_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LobbySort {
+    #[default]
+    Players,
+    Stakes,
+    AvgPot,
+    HandsPerHour,
+    Newest,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LobbyFilter {
+    pub variant: Option<String>,
+    pub min_big_blind: Option<i64>,
+    pub max_big_blind: Option<i64>,
+    #[serde(default)]
+    pub has_open_seat: bool,
+    #[serde(default)]
+    pub sort: LobbySort,
+    #[serde(default)]
+    pub ascending: bool, // biggest first otherwise
+}
+
+impl LobbyFilter {
+    pub fn matches(&self, table: &LobbyTable) -> bool {
+        self.variant.as_deref().is_none_or(|v| v == table.variant)
+            && self.min_big_blind.is_none_or(|bb| table.big_blind >= bb)
+            && self.max_big_blind.is_none_or(|bb| table.big_blind <= bb)
+            && (!self.has_open_seat || table.seated < table.max_players)
+    }
+
+    pub fn apply(&self, tables: Vec<LobbyTable>) -> Vec<LobbyTable> {
+        let mut tables: Vec<LobbyTable> = tables.into_iter().filter(|t| self.matches(t)).collect();
+        let key = |t: &LobbyTable| match self.sort {
+            LobbySort::Players => t.seated as i64,
+            LobbySort::Stakes => t.big_blind,
+            LobbySort::AvgPot => t.avg_pot,
+            LobbySort::HandsPerHour => t.hands_per_hour,
+            LobbySort::Newest => t.created_at,
+        };
+        tables.sort_by_key(|t| key(t));
+        if !self.ascending {
+            tables.reverse();
+        }
+        tables
+    }
+}
+
+// joins the stored rooms with who is sitting at them right now and their recent hands
+pub async fn lobby_tables(gm: &GameManager, rooms: Vec<Rooms>) -> anyhow::Result<Vec<LobbyTable>> {
+    let ids = rooms.iter().map(|r| r.id).collect::<Vec<_>>();
+    let since = Utc::now() - Duration::hours(1);
+    let stats = hand_stats_by_room(&gm.pool, since, &ids).await?;
+    let mut tables = Vec::with_capacity(rooms.len());
+    for room in rooms {
+        let recent = stats.iter().find(|s| s.room_id == room.id);
+        let (seated, max_players) = match gm.occupancy(room.id).await {
+            Some(live) => live,
+            // nobody has joined since the server started
+            None => (
+                0,
+                room.max_players.map_or(DEFAULT_MAX_PLAYERS, |n| {
+                    n.clamp(2, MAX_SEATS as i16) as usize
+                }),
+            ),
+        };
+        tables.push(LobbyTable {
+            room_id: room.id,
+            room_name: room.room_name,
+            room_status: room.room_status,
+            variant: room.variant,
+            betting_limit: room.betting_limit,
+            small_blind: room.small_blind,
+            big_blind: room.big_blind,
+            ante: room.ante,
+            min_buy_in: room.min_buy_in,
+            max_buy_in: room.max_buy_in,
+            rake_bps: room.rake_bps,
+            seated,
+            max_players,
+            avg_pot: recent.map_or(0, |s| s.avg_pot),
+            hands_per_hour: recent.map_or(0, |s| s.hands),
+            created_at: room.created_at.timestamp_millis(),
+        });
+    }
+    Ok(tables)
+}
+
+// every table players can still sit down at
+pub async fn open_tables(gm: &GameManager) -> anyhow::Result<Vec<LobbyTable>> {
+    let mut rooms = Vec::new();
+    for status in OPEN_STATUSES {
+        rooms.extend(list_by_status(&gm.pool, status).await?);
+    }
+    lobby_tables(gm, rooms).await
+}
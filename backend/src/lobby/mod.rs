@@ -0,0 +1,10 @@
+pub mod handlers;
+pub mod listing;
+
+use actix_web::web;
+
+use crate::lobby::handlers::get_lobby;
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("").route(web::get().to(get_lobby)));
+}
@@ -1,17 +1,35 @@
-use crate::{config::Setting, routes::init_routes, state::AppState, telemetry::init_tracing};
+use crate::{
+    config::Setting, game_manager::GameManager, routes::init_routes, state::AppState,
+    telemetry::init_tracing,
+};
 use actix_web::{App, HttpServer, middleware::Logger, web};
 use anyhow::Ok;
-use database::create_pool;
+use dashmap::DashMap;
+use database::{create_pool, models::check_ledger};
+use std::sync::Arc;
 use tracing::info;
 
 mod auth;
 mod config;
+mod deck;
+mod equity;
 mod errors;
+mod evaluator;
+mod event_log;
+mod fairness;
 mod game_manager;
+mod hands;
+mod lobby;
+mod notation;
 mod poker_engine;
+mod rake;
+mod range;
+mod rooms;
 mod routes;
 mod state;
 mod telemetry;
+mod tools;
+mod views;
 mod ws_server;
 
 #[actix_web::main]
@@ -24,15 +42,39 @@ async fn main() -> anyhow::Result<()> {
         &setting.bind_addr, &setting.worker_threads
     );
 
-    let pool = create_pool(&setting.database_url).await?;
+    let pool = create_pool(&setting.database_url, setting.default_max_connections).await?;
+    let ledger = check_ledger(&pool).await?;
+    if !ledger.conserved() {
+        tracing::error!("chip ledger doesn't add up: {:?}", ledger);
+    }
 
     let app_state = AppState::new(pool.clone(), setting.clone()).await?;
     let app_data = web::Data::new(app_state.clone());
 
+    let redis = redis::Client::open(setting.redis_url.as_str())?
+        .get_connection_manager()
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to connect to redis: {}", e))?;
+    let game_manager = GameManager::new(
+        pool.clone(),
+        redis,
+        Arc::new(DashMap::new()),
+        setting.clone(),
+    )
+    .await;
+    let game_data = web::Data::new(game_manager);
+
     let server = HttpServer::new(move || {
         App::new()
-            .wrap(Logger::default())
+            // Logger::default() with the query string left out, sockets may carry a token there
+            .wrap(
+                Logger::new("%a \"%{request}xi\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %T")
+                    .custom_request_replace("request", |req| {
+                        format!("{} {} {:?}", req.method(), req.path(), req.version())
+                    }),
+            )
             .app_data(app_data.clone())
+            .app_data(game_data.clone())
             .configure(init_routes)
     })
     .workers(setting.worker_threads)
@@ -49,7 +91,7 @@ async fn main() -> anyhow::Result<()> {
     });
 
     server.await?;
-    let _ = shutdown_listener.await?;
+    shutdown_listener.await?;
     info!("!!Server Stopped Cleanly");
     Ok(())
 }
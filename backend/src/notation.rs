@@ -0,0 +1,158 @@
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use thiserror::Error;
+
+use crate::poker_engine::{Card, Suit};
+
+// canonical notation is rank then lowercase suit: "Ah", "Td", "2c"
+// parsing is lenient: "10s", "AH", "a♥" and "Qd" are all accepted
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CardParseError {
+    #[error("empty card")]
+    Empty,
+    #[error("invalid rank in {0:?}")]
+    InvalidRank(String),
+    #[error("invalid suit in {0:?}")]
+    InvalidSuit(String),
+    #[error("more than one card in {0:?}")]
+    TrailingInput(String),
+    #[error("card {0} appears more than once")]
+    Duplicate(Card),
+    #[error("a board has 0, 3, 4 or 5 cards, got {0}")]
+    BoardSize(usize),
+    #[error("expected a json array of card strings")]
+    NotAnArray,
+}
+
+pub fn rank_char(rank: u8) -> char {
+    match rank {
+        10 => 'T',
+        11 => 'J',
+        12 => 'Q',
+        13 => 'K',
+        14 => 'A',
+        v => (b'0' + v) as char,
+    }
+}
+
+pub fn parse_rank(c: char) -> Option<u8> {
+    match c.to_ascii_uppercase() {
+        '2'..='9' => Some(c as u8 - b'0'),
+        'T' => Some(10),
+        'J' => Some(11),
+        'Q' => Some(12),
+        'K' => Some(13),
+        'A' => Some(14),
+        _ => None,
+    }
+}
+
+pub fn suit_char(suit: Suit) -> char {
+    match suit {
+        Suit::Clubs => 'c',
+        Suit::Diamonds => 'd',
+        Suit::Hearts => 'h',
+        Suit::Spades => 's',
+    }
+}
+
+pub fn parse_suit(c: char) -> Option<Suit> {
+    match c {
+        'c' | 'C' | '♣' | '♧' => Some(Suit::Clubs),
+        'd' | 'D' | '♦' | '♢' => Some(Suit::Diamonds),
+        'h' | 'H' | '♥' | '♡' => Some(Suit::Hearts),
+        's' | 'S' | '♠' | '♤' => Some(Suit::Spades),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Suit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", suit_char(*self))
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", rank_char(self.rank), suit_char(self.suit))
+    }
+}
+
+impl FromStr for Card {
+    type Err = CardParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut chars = s.chars().peekable();
+        let card = next_card(&mut chars, s)?.ok_or(CardParseError::Empty)?;
+        if chars.next().is_some() {
+            return Err(CardParseError::TrailingInput(s.to_string()));
+        }
+        Ok(card)
+    }
+}
+
+// reads one card, skipping separators in front of it. "10" is taken as a ten
+fn next_card(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    input: &str,
+) -> Result<Option<Card>, CardParseError> {
+    while chars.peek().is_some_and(|c| c.is_whitespace() || *c == ',') {
+        chars.next();
+    }
+    let Some(first) = chars.next() else {
+        return Ok(None);
+    };
+    let rank = if first == '1' && chars.peek() == Some(&'0') {
+        chars.next();
+        10
+    } else {
+        parse_rank(first).ok_or_else(|| CardParseError::InvalidRank(input.to_string()))?
+    };
+    let suit = chars
+        .next()
+        .and_then(parse_suit)
+        .ok_or_else(|| CardParseError::InvalidSuit(input.to_string()))?;
+    Ok(Some(Card { rank, suit }))
+}
+
+// "AhKd Qc", "Ah,Kd,Qc" and "AhKdQc" all parse to the same three cards
+pub fn parse_cards(s: &str) -> Result<Vec<Card>, CardParseError> {
+    let mut chars = s.chars().peekable();
+    let mut out = Vec::new();
+    let mut seen = HashSet::new();
+    while let Some(card) = next_card(&mut chars, s)? {
+        if !seen.insert(card) {
+            return Err(CardParseError::Duplicate(card));
+        }
+        out.push(card);
+    }
+    Ok(out)
+}
+
+pub fn parse_board(s: &str) -> Result<Vec<Card>, CardParseError> {
+    let cards = parse_cards(s)?;
+    match cards.len() {
+        0 | 3 | 4 | 5 => Ok(cards),
+        n => Err(CardParseError::BoardSize(n)),
+    }
+}
+
+pub fn format_cards(cards: &[Card]) -> String {
+    cards.iter().map(|c| c.to_string()).collect::<String>()
+}
+
+// hand_players.hole_cards and hands.board are stored as ["Ah", "Kd"]
+pub fn cards_to_json(cards: &[Card]) -> serde_json::Value {
+    serde_json::json!(cards.iter().map(|c| c.to_string()).collect::<Vec<_>>())
+}
+
+pub fn cards_from_json(value: &serde_json::Value) -> Result<Vec<Card>, CardParseError> {
+    value
+        .as_array()
+        .ok_or(CardParseError::NotAnArray)?
+        .iter()
+        .map(|v| v.as_str().ok_or(CardParseError::NotAnArray)?.parse())
+        .collect()
+}
@@ -1,8 +1,9 @@
 use std::{cmp::Ordering, collections::HashMap};
 
-use argon2::password_hash::rand_core::{OsRng, RngCore};
-use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::evaluator;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Card {
@@ -18,42 +19,55 @@ pub enum Suit {
     Spades,
 }
 
-impl Card {
-    pub fn to_string(&self) -> String {
-        let r = match self.rank {
-            11 => "J".to_string(),
-            12 => "Q".to_string(),
-            13 => "K".to_string(),
-            14 => "A".to_string(),
-            v => v.to_string(),
-        };
-
-        let s = match self.suit {
-            Suit::Clubs => "C".to_string(),
-            Suit::Diamonds => "D".to_string(),
-            Suit::Hearts => "H".to_string(),
-            Suit::Spades => "S".to_string(),
-        };
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandRank {
+    category: u8, // 8 = straight flush , 7 = four of a kind , 6 = full house , 5 = flush , 4 = straight , 3 = three of a kind , 2 = two pair , 1 = one pair , 0 = high card
+    order: u8,    // where the category ranks under the rules it was evaluated with
+    tiebreakers: Vec<u8>, // in decreasing order of the rank (14..2)
+}
 
-        format!("{}{}", r, s)
-    }
+// what changes between variants when ranking five card hands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandRules {
+    pub lowest_rank: u8, // an ace below this rank and the next four make the lowest straight
+    pub flush_beats_full_house: bool,
 }
 
-impl From<&str> for Card {
-    fn from(_s: &str) -> Self {
-        unimplemented!()
+impl HandRules {
+    pub const STANDARD: HandRules = HandRules {
+        lowest_rank: 2,
+        flush_beats_full_house: false,
+    };
+    // 36 card deck, A-6-7-8-9 is the lowest straight and flushes are rarer than full houses
+    pub const SHORT_DECK: HandRules = HandRules {
+        lowest_rank: 6,
+        flush_beats_full_house: true,
+    };
+
+    fn rank(self, category: u8, tiebreakers: Vec<u8>) -> HandRank {
+        let order = match category {
+            5 if self.flush_beats_full_house => 6,
+            6 if self.flush_beats_full_house => 5,
+            c => c,
+        };
+        HandRank {
+            category,
+            order,
+            tiebreakers,
+        }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct HandRank {
-    category: u8, // 8 = straight flush , 7 = four of a kind , 6 = full house , 5 = flush , 4 = straight , 3 = three of a kind , 2 = two pair , 1 = one pair , 0 = high card
-    tiebreakers: Vec<u8>, // in decreasing order of the rank (14..2)
+impl HandRank {
+    #[cfg(test)]
+    pub fn category(&self) -> u8 {
+        self.category
+    }
 }
 
 impl Ord for HandRank {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        match self.category.cmp(&other.category) {
+        match self.order.cmp(&other.order) {
             std::cmp::Ordering::Equal => self.tiebreakers.cmp(&other.tiebreakers),
             ord => ord,
         }
@@ -65,35 +79,326 @@ impl PartialOrd for HandRank {
     }
 }
 
-pub fn new_deck() -> Vec<Card> {
+pub fn new_deck(variant: GameVariant) -> Vec<Card> {
     let mut deck = Vec::with_capacity(52);
     for &s in &[Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades] {
-        for r in 2u8..=14u8 {
+        for r in variant.hand_rules().lowest_rank..=14u8 {
             deck.push(Card { rank: r, suit: s });
         }
     }
     deck
 }
 
-pub fn shuffle_deck(deck: &mut [Card]) {
-    let mut seed = [0u8; 32];
-    OsRng.fill_bytes(&mut seed); //generates a random cryptographic bytes based on Os/System entropy
-    let mut rng = StdRng::from_seed(seed); // generates a random number 
-    deck.shuffle(&mut rng);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Street {
+    PreFlop,
+    Flop,
+    Turn,
+    River,
+    // stud games, named after how many cards each player holds
+    Third,
+    Fourth,
+    Fifth,
+    Sixth,
+    Seventh,
+    Showdown,
 }
 
-pub fn deal_hole_cards(deck: &mut Vec<Card>, seats: usize) -> Vec<Option<(Card, Card)>> {
-    let mut out = vec![None; seats];
-    let mut index = 0;
-    for i in 0..seats {
-        if deck.len() < index + 2 {
-            break;
+impl Street {
+    pub fn next(self) -> Street {
+        match self {
+            Street::PreFlop => Street::Flop,
+            Street::Flop => Street::Turn,
+            Street::Turn => Street::River,
+            Street::Third => Street::Fourth,
+            Street::Fourth => Street::Fifth,
+            Street::Fifth => Street::Sixth,
+            Street::Sixth => Street::Seventh,
+            Street::River | Street::Seventh | Street::Showdown => Street::Showdown,
         }
-        out[i] = Some((deck[index], deck[index + 1]));
-        index += 2
     }
-    deck.drain(0..seats);
-    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameVariant {
+    #[default]
+    Holdem,
+    Omaha,     // pot-limit omaha, 4 hole cards
+    Omaha5,    // 5 hole cards
+    OmahaHiLo, // 4 hole cards, split between the best high and the best eight or better low
+    ShortDeck, // 6+ hold'em, no twos to fives in the deck
+    Stud,      // seven card stud, no board, every player gets own down and up cards
+    StudHiLo,  // stud split with an eight or better low
+    Razz,      // stud played for the best ace-to-five low only
+}
+
+impl GameVariant {
+    // the value stored in rooms.variant
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GameVariant::Holdem => "holdem",
+            GameVariant::Omaha => "omaha",
+            GameVariant::Omaha5 => "omaha5",
+            GameVariant::OmahaHiLo => "omaha_hilo",
+            GameVariant::ShortDeck => "short_deck",
+            GameVariant::Stud => "stud",
+            GameVariant::StudHiLo => "stud_hilo",
+            GameVariant::Razz => "razz",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GameVariant> {
+        match name {
+            "holdem" => Some(GameVariant::Holdem),
+            "omaha" => Some(GameVariant::Omaha),
+            "omaha5" => Some(GameVariant::Omaha5),
+            "omaha_hilo" => Some(GameVariant::OmahaHiLo),
+            "short_deck" => Some(GameVariant::ShortDeck),
+            "stud" => Some(GameVariant::Stud),
+            "stud_hilo" => Some(GameVariant::StudHiLo),
+            "razz" => Some(GameVariant::Razz),
+            _ => None,
+        }
+    }
+
+    // cards every player is dealt when the hand starts, stud games deal two down and one up
+    pub fn hole_cards(self) -> usize {
+        match self {
+            GameVariant::Holdem | GameVariant::ShortDeck => 2,
+            GameVariant::Stud | GameVariant::StudHiLo | GameVariant::Razz => 3,
+            GameVariant::Omaha | GameVariant::OmahaHiLo => 4,
+            GameVariant::Omaha5 => 5,
+        }
+    }
+
+    // most players one deck can deal a whole hand to. flop games need three burns and five
+    // board cards too, stud needs six cards a player since the seventh can be one shared card
+    pub fn max_players(self) -> usize {
+        let deck = new_deck(self).len();
+        if self.is_stud() {
+            (deck - 1) / 6
+        } else {
+            (deck - 8) / self.hole_cards()
+        }
+    }
+
+    pub fn is_stud(self) -> bool {
+        matches!(
+            self,
+            GameVariant::Stud | GameVariant::StudHiLo | GameVariant::Razz
+        )
+    }
+
+    pub fn first_street(self) -> Street {
+        if self.is_stud() {
+            Street::Third
+        } else {
+            Street::PreFlop
+        }
+    }
+
+    pub fn betting_limit(self) -> BettingLimit {
+        match self {
+            GameVariant::Holdem | GameVariant::ShortDeck => BettingLimit::NoLimit,
+            GameVariant::Omaha | GameVariant::Omaha5 | GameVariant::OmahaHiLo => {
+                BettingLimit::PotLimit
+            }
+            GameVariant::Stud | GameVariant::StudHiLo | GameVariant::Razz => {
+                BettingLimit::FixedLimit
+            }
+        }
+    }
+
+    pub fn hand_rules(self) -> HandRules {
+        match self {
+            GameVariant::ShortDeck => HandRules::SHORT_DECK,
+            _ => HandRules::STANDARD,
+        }
+    }
+
+    // best hand for a seat, omaha has to use exactly two hole cards and three from the board.
+    // stud hands are all in `hole`, the board only holds a shared card if the deck ran short
+    pub fn evaluate(self, hole: &[Card], board: &[Card]) -> HandRank {
+        match self {
+            GameVariant::Holdem | GameVariant::Stud | GameVariant::StudHiLo | GameVariant::Razz => {
+                let cards = [hole, board].concat();
+                evaluate_best_of_seven(&cards)
+            }
+            GameVariant::ShortDeck => {
+                let rules = self.hand_rules();
+                let cards = [hole, board].concat();
+                evaluator::hand_rank_with(evaluator::evaluate_with(&cards, rules), rules)
+            }
+            GameVariant::Omaha | GameVariant::Omaha5 | GameVariant::OmahaHiLo => {
+                evaluator::hand_rank(evaluator::evaluate_omaha(hole, board))
+            }
+        }
+    }
+
+    pub fn is_hi_lo(self) -> bool {
+        matches!(self, GameVariant::OmahaHiLo | GameVariant::StudHiLo)
+    }
+
+    // only the low counts, the whole pot goes to it
+    pub fn is_lowball(self) -> bool {
+        matches!(self, GameVariant::Razz)
+    }
+
+    // the qualifying low for split games, None when the seat has no eight or better.
+    // razz takes any low, there is no qualifier
+    pub fn evaluate_low(self, hole: &[Card], board: &[Card]) -> Option<LowRank> {
+        let low = match self {
+            GameVariant::OmahaHiLo => best_low(&omaha_hands(hole, board))?,
+            GameVariant::StudHiLo | GameVariant::Razz => {
+                best_low(&five_card_hands(&[hole, board].concat()))?
+            }
+            _ => return None,
+        };
+        (self.is_lowball() || low.qualifies(8)).then_some(low)
+    }
+
+    // stud: the lowest upcard brings it in (the highest in razz, where aces are low),
+    // ties go by suit with clubs lowest and spades highest
+    pub fn bring_in_seat(self, upcards: &[Option<Card>]) -> Option<usize> {
+        let key = |c: &Card| {
+            let suit = c.suit as u8;
+            if self.is_lowball() {
+                let rank = if c.rank == 14 { 1 } else { c.rank };
+                (14 - rank, 3 - suit)
+            } else {
+                (c.rank, suit)
+            }
+        };
+        (0..upcards.len())
+            .filter_map(|i| upcards[i].as_ref().map(|c| (key(c), i)))
+            .min()
+            .map(|(_, i)| i)
+    }
+
+    // stud: from fourth street the best hand showing opens the betting, the lowest one in razz.
+    // ties go to the first seat
+    pub fn best_showing(self, upcards: &[Vec<Card>], in_hand: &[bool]) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for seat in (0..upcards.len()).filter(|&i| in_hand[i] && !upcards[i].is_empty()) {
+            let better = match best {
+                None => true,
+                Some(b) if self.is_lowball() => low_rank(&upcards[seat]) > low_rank(&upcards[b]),
+                Some(b) => showing_rank(&upcards[seat]) > showing_rank(&upcards[b]),
+            };
+            if better {
+                best = Some(seat);
+            }
+        }
+        best
+    }
+}
+
+// up to four upcards only make pairs, trips or quads, straights and flushes don't count yet
+fn showing_rank(cards: &[Card]) -> HandRank {
+    let mut counts = [0u8; 15];
+    for c in cards {
+        counts[c.rank as usize] += 1;
+    }
+    let mut groups = (2..15u8)
+        .filter(|&r| counts[r as usize] > 0)
+        .map(|r| (counts[r as usize], r))
+        .collect::<Vec<_>>();
+    groups.sort_by(|a, b| b.cmp(a));
+    let category = match (groups[0].0, groups.get(1).map(|g| g.0)) {
+        (4, _) => 7,
+        (3, _) => 3,
+        (2, Some(2)) => 2,
+        (2, _) => 1,
+        _ => 0,
+    };
+    let tiebreakers = groups.iter().map(|&(_, r)| r).collect();
+    HandRules::STANDARD.rank(category, tiebreakers)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::enum_variant_names)] // the names poker players use
+pub enum BettingLimit {
+    #[default]
+    NoLimit,
+    PotLimit,   // a raise can be at most the pot after calling
+    FixedLimit, // every bet and raise is exactly min_bet, at most raise_cap of them per street
+}
+
+impl BettingLimit {
+    pub fn from_name(name: &str) -> Option<BettingLimit> {
+        match name {
+            "no_limit" => Some(BettingLimit::NoLimit),
+            "pot_limit" => Some(BettingLimit::PotLimit),
+            "fixed_limit" => Some(BettingLimit::FixedLimit),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlindPositions {
+    pub button: usize,
+    pub small_blind: usize, // may point at an empty seat, then the small blind is dead
+    pub big_blind: usize,
+}
+
+// dead button rules: the big blind always moves to the next active seat, the small blind
+// takes last hand's big blind seat and the button last hand's small blind seat, even if
+// those seats have been emptied since. heads up the button posts the small blind
+pub fn blind_positions(
+    active: &[bool],
+    previous: Option<BlindPositions>,
+) -> Option<BlindPositions> {
+    let n = active.len();
+    let players = active.iter().filter(|&&a| a).count();
+    if players < 2 {
+        return None;
+    }
+    let next_active = |from: usize| {
+        (1..=n)
+            .map(|k| (from + k) % n)
+            .find(|&i| active[i])
+            .unwrap_or(from)
+    };
+
+    let Some(prev) = previous else {
+        let button = next_active(n - 1);
+        let small_blind = if players == 2 {
+            button
+        } else {
+            next_active(button)
+        };
+        return Some(BlindPositions {
+            button,
+            small_blind,
+            big_blind: next_active(small_blind),
+        });
+    };
+
+    let big_blind = next_active(prev.big_blind);
+    if players == 2 {
+        let button = next_active(big_blind);
+        return Some(BlindPositions {
+            button,
+            small_blind: button,
+            big_blind,
+        });
+    }
+    Some(BlindPositions {
+        button: prev.small_blind,
+        small_blind: prev.big_blind,
+        big_blind,
+    })
+}
+
+pub fn burn_card(deck: &mut Vec<Card>) -> Option<Card> {
+    if deck.is_empty() {
+        return None;
+    }
+    Some(deck.remove(0))
 }
 
 pub fn deal_flop(deck: &mut Vec<Card>) -> Vec<Card> {
@@ -119,7 +424,7 @@ pub fn deal_river(deck: &mut Vec<Card>) -> Option<Card> {
     Some(deck.remove(0))
 }
 
-pub fn evaluate_five(cards: &[Card; 5]) -> HandRank {
+pub fn evaluate_five_with(cards: &[Card; 5], rules: HandRules) -> HandRank {
     let mut ranks = cards.iter().map(|c| c.rank).collect::<Vec<u8>>();
     ranks.sort_by(|a, b| b.cmp(a)); //descending
 
@@ -140,74 +445,55 @@ pub fn evaluate_five(cards: &[Card; 5]) -> HandRank {
     let mut top_straight = 0u8;
     let mut ranks_for_straight = unique_ranks.clone();
     if unique_ranks.contains(&14) {
-        ranks_for_straight.push(1);
+        ranks_for_straight.push(rules.lowest_rank - 1);
     }
 
     ranks_for_straight.sort_by(|a, b| b.cmp(a));
 
-    for i in 0..=(ranks_for_straight.len().saturating_sub(5)) {
-        let ok = true;
-        for j in 0..4 {
-            if ranks_for_straight[i + j] != ranks_for_straight[i + j + 1] + 1 {
-                is_straight = false;
+    if ranks_for_straight.len() >= 5 {
+        for i in 0..=(ranks_for_straight.len() - 5) {
+            let ok = (0..4).all(|j| ranks_for_straight[i + j] == ranks_for_straight[i + j + 1] + 1);
+            if ok {
+                is_straight = true;
+                top_straight = ranks_for_straight[i];
                 break;
             }
         }
-        if ok {
-            is_straight = true;
-            top_straight = ranks_for_straight[i];
-            break;
-        }
     }
 
     let mut counts = freq.iter().map(|(&r, &c)| (c as u8, r)).collect::<Vec<_>>();
-    counts.sort_by(|a, b| match a.0.cmp(&b.0) {
+    counts.sort_by(|a, b| match b.0.cmp(&a.0) {
         Ordering::Equal => b.1.cmp(&a.1),
         ord => ord,
     });
 
     //straight flush
     if is_flush && is_straight {
-        return HandRank {
-            category: 8,
-            tiebreakers: vec![top_straight],
-        };
+        return rules.rank(8, vec![top_straight]);
     }
 
     //four of a kind
     if counts[0].0 == 4 {
         let four_rank = counts[0].1;
         let kicker = *ranks.iter().find(|&&r| r != four_rank).unwrap();
-        return HandRank {
-            category: 7,
-            tiebreakers: vec![four_rank, kicker],
-        };
+        return rules.rank(7, vec![four_rank, kicker]);
     }
 
     //full house 3 + 2
     if counts[0].0 == 3 && counts.len() >= 2 && counts[1].0 == 2 {
         let triple = counts[0].1;
         let pair = counts[1].1;
-        return HandRank {
-            category: 6,
-            tiebreakers: vec![triple, pair],
-        };
+        return rules.rank(6, vec![triple, pair]);
     }
 
     //flush
     if is_flush {
-        return HandRank {
-            category: 5,
-            tiebreakers: ranks.clone(),
-        };
+        return rules.rank(5, ranks.clone());
     }
 
     //straight
     if is_straight {
-        return HandRank {
-            category: 4,
-            tiebreakers: vec![top_straight],
-        };
+        return rules.rank(4, vec![top_straight]);
     }
 
     //Three of a kind
@@ -218,10 +504,7 @@ pub fn evaluate_five(cards: &[Card; 5]) -> HandRank {
             .filter(|&&r| r != triple)
             .cloned()
             .collect::<Vec<_>>();
-        return HandRank {
-            category: 3,
-            tiebreakers: [vec![triple], kickers].concat(),
-        };
+        return rules.rank(3, [vec![triple], kickers].concat());
     }
 
     //two pair
@@ -232,10 +515,7 @@ pub fn evaluate_five(cards: &[Card; 5]) -> HandRank {
             .iter()
             .find(|&&r| r != low_pair && r != high_pair)
             .unwrap();
-        HandRank {
-            category: 2,
-            tiebreakers: vec![high_pair, low_pair, kicker],
-        };
+        return rules.rank(2, vec![high_pair, low_pair, kicker]);
     }
 
     //one pair
@@ -246,58 +526,869 @@ pub fn evaluate_five(cards: &[Card; 5]) -> HandRank {
             .filter(|&&r| r != pair_rank)
             .cloned()
             .collect::<Vec<_>>();
-        return HandRank {
-            category: 1,
-            tiebreakers: [vec![pair_rank], kickers].concat(),
-        };
+        return rules.rank(1, [vec![pair_rank], kickers].concat());
     }
 
     //high card
-    HandRank {
-        category: 0,
-        tiebreakers: ranks.clone(),
+    rules.rank(0, ranks.clone())
+}
+
+// ace-to-five low: aces play low, straights and flushes don't count against the hand.
+// a better low compares greater, so lows can be awarded with the same max() as HandRank
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LowRank {
+    paired: u8, // 0 = five different ranks, 1 = pair, 2 = two pair, 3 = trips, 4 = full house, 5 = quads
+    ranks: Vec<u8>, // ace as 1, bigger groups first, then highest rank first
+}
+
+impl LowRank {
+    // "eight or better" is limit 8: five different ranks, none above it
+    pub fn qualifies(&self, limit: u8) -> bool {
+        self.paired == 0 && self.ranks[0] <= limit
     }
 }
 
-pub fn evaluate_best_of_seven(cards: &Vec<Card>) -> HandRank {
+impl Ord for LowRank {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.paired, &other.ranks).cmp(&(self.paired, &self.ranks))
+    }
+}
+
+impl PartialOrd for LowRank {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+pub fn evaluate_low_five(cards: &[Card; 5]) -> LowRank {
+    low_rank(cards)
+}
+
+// ace-to-five ranking of any number of cards, razz uses it on the upcards as well
+pub fn low_rank(cards: &[Card]) -> LowRank {
+    let mut counts = [0u8; 15];
+    for c in cards {
+        counts[if c.rank == 14 { 1 } else { c.rank as usize }] += 1;
+    }
+    let mut groups = (1..15u8)
+        .filter(|&r| counts[r as usize] > 0)
+        .map(|r| (counts[r as usize], r))
+        .collect::<Vec<_>>();
+    groups.sort_by(|a, b| b.cmp(a));
+    let paired = match (groups[0].0, groups.get(1).map(|g| g.0)) {
+        (1, _) => 0,
+        (2, Some(2)) => 2,
+        (2, _) => 1,
+        (3, Some(2)) => 4,
+        (3, _) => 3,
+        _ => 5,
+    };
+    let ranks = groups
+        .iter()
+        .flat_map(|&(n, r)| std::iter::repeat_n(r, n as usize))
+        .collect();
+    LowRank { paired, ranks }
+}
+
+// every five card hand out of the given cards
+pub fn five_card_hands(cards: &[Card]) -> Vec<[Card; 5]> {
+    let mut out = Vec::new();
+    let n = cards.len();
+    for a in 0..n {
+        for b in a + 1..n {
+            for c in b + 1..n {
+                for d in c + 1..n {
+                    for e in d + 1..n {
+                        out.push([cards[a], cards[b], cards[c], cards[d], cards[e]]);
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+// every hand made of exactly two hole cards and three board cards
+pub fn omaha_hands(hole: &[Card], board: &[Card]) -> Vec<[Card; 5]> {
+    let mut out = Vec::new();
+    for (i, &a) in hole.iter().enumerate() {
+        for &b in &hole[i + 1..] {
+            for [c, d, e] in three_card_sets(board) {
+                out.push([a, b, c, d, e]);
+            }
+        }
+    }
+    out
+}
+
+fn three_card_sets(cards: &[Card]) -> Vec<[Card; 3]> {
+    let mut out = Vec::new();
+    for (i, &a) in cards.iter().enumerate() {
+        for (j, &b) in cards.iter().enumerate().skip(i + 1) {
+            for &c in &cards[j + 1..] {
+                out.push([a, b, c]);
+            }
+        }
+    }
+    out
+}
+
+pub fn best_low(hands: &[[Card; 5]]) -> Option<LowRank> {
+    hands.iter().map(evaluate_low_five).max()
+}
+
+// best five card hand out of 5 to 7 cards, backed by the lookup tables in evaluator
+pub fn evaluate_best_of_seven(cards: &[Card]) -> HandRank {
     let n = cards.len();
     assert!(
-        n >= 5 && n <= 7,
+        (5..=7).contains(&n),
         "cards length must be less than 7 and greater than 5"
     );
-    let mut best = None;
-    let mut indexes = Vec::new();
-    for a in 0..n {
-        for b in a..n {
-            for c in b..n {
-                for d in c..n {
-                    for e in d..n {
-                        indexes.clear();
-                        indexes.push(a);
-                        indexes.push(b);
-                        indexes.push(c);
-                        indexes.push(d);
-                        indexes.push(e);
-                        let hand = [
-                            cards[indexes[0]],
-                            cards[indexes[1]],
-                            cards[indexes[2]],
-                            cards[indexes[3]],
-                            cards[indexes[4]],
-                        ];
-                        let rank = evaluate_five(&hand);
-                        match best {
-                            None => best = Some(rank),
-                            Some(ref cur) => {
-                                if rank > *cur {
-                                    best = Some(rank)
-                                }
-                            } // this comparison is based on ord trait implemented on HandRank it first compares against category then tiebreakers
-                        }
-                    }
+    evaluator::hand_rank(evaluator::evaluate(cards))
+}
+
+// as clients send it, {"action": "raise", "amount": 40}, only bet and raise carry an amount
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", content = "amount", rename_all = "snake_case")]
+pub enum PlayerAction {
+    Fold,
+    Check,
+    Call,
+    Bet(i64),   // total chips committed on this street after the bet
+    Raise(i64), // raise to (total for the street), not raise by
+    #[serde(rename = "allin")]
+    AllIn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LegalAction {
+    Fold,
+    Check,
+    Call { amount: i64 },
+    Bet { min: i64, max: i64 },   // street totals
+    Raise { min: i64, max: i64 }, // street totals
+    AllIn { amount: i64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BettingError {
+    #[error("it's not player's turn")]
+    NotYourTurn,
+    #[error("player is not active in this round")]
+    PlayerNotActive,
+    #[error("cannot check facing a bet of {to_call}")]
+    CannotCheck { to_call: i64 },
+    #[error("nothing to call")]
+    NothingToCall,
+    #[error("there is already a bet, raise instead")]
+    BetFacingBet,
+    #[error("there is no bet to raise")]
+    NoBetToRaise,
+    #[error("raising is not allowed for this player")]
+    RaiseNotAllowed,
+    #[error("betting is capped at {cap} bets and raises this street")]
+    RaiseCapReached { cap: usize },
+    #[error("amount {amount} is below the minimum of {min}")]
+    BelowMinimum { min: i64, amount: i64 },
+    #[error("amount {amount} exceeds the available {max}")]
+    ExceedsStack { max: i64, amount: i64 },
+    #[error("amount {amount} exceeds the limit of {max}")]
+    ExceedsLimit { max: i64, amount: i64 },
+    #[error("betting round is closed")]
+    RoundClosed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedAction {
+    pub action: PlayerAction, // normalized, all-ins are turned into call / bet / raise
+    pub chips_added: i64,
+    pub all_in: bool,
+}
+
+impl AppliedAction {
+    // action_type as it is stored in the actions table
+    pub fn name(&self) -> &'static str {
+        match self.action {
+            _ if self.all_in => "allin",
+            PlayerAction::Fold => "fold",
+            PlayerAction::Check => "check",
+            PlayerAction::Call => "call",
+            PlayerAction::Bet(_) => "bet",
+            PlayerAction::Raise(_) => "raise",
+            PlayerAction::AllIn => "allin",
+        }
+    }
+}
+
+// per street betting state, everything is indexed by seat
+#[derive(Debug, Clone)]
+pub struct BettingRound {
+    pub stacks: Vec<i64>,    // chips behind
+    pub committed: Vec<i64>, // chips put in on this street
+    pub invested: Vec<i64>,  // chips put in over the whole hand, used to build the pots
+    pub in_hand: Vec<bool>,  // false once folded
+    pub acted: Vec<bool>,
+    pub faced: Vec<i64>, // current_bet at the time the seat last acted
+    pub current_bet: i64,
+    pub last_raise: i64, // size of the last full bet / raise
+    pub min_bet: i64,
+    pub limit: BettingLimit,
+    pub raise_cap: usize, // fixed limit only
+    pub raises: usize,    // full bets and raises on this street, a live big blind counts as the bet
+    pub to_act: Option<usize>,
+}
+
+impl BettingRound {
+    pub fn new(
+        stacks: Vec<i64>,
+        in_hand: Vec<bool>,
+        min_bet: i64,
+        limit: BettingLimit,
+        raise_cap: usize,
+        first_to_act: usize,
+    ) -> Self {
+        let n = stacks.len();
+        let mut round = BettingRound {
+            stacks,
+            committed: vec![0; n],
+            invested: vec![0; n],
+            in_hand,
+            acted: vec![false; n],
+            faced: vec![0; n],
+            current_bet: 0,
+            last_raise: min_bet,
+            min_bet,
+            limit,
+            raise_cap,
+            raises: 0,
+            to_act: None,
+        };
+        round.to_act = round.first_can_act(first_to_act);
+        round
+    }
+
+    pub fn can_act(&self, seat: usize) -> bool {
+        self.in_hand[seat] && self.stacks[seat] > 0
+    }
+
+    pub fn players_in_hand(&self) -> usize {
+        self.in_hand.iter().filter(|&&p| p).count()
+    }
+
+    pub fn to_call(&self, seat: usize) -> i64 {
+        (self.current_bet - self.committed[seat])
+            .max(0)
+            .min(self.stacks[seat])
+    }
+
+    // a player that already acted may only raise again if a full raise happened since
+    fn raise_reopened(&self, seat: usize) -> bool {
+        !self.acted[seat] || self.current_bet - self.faced[seat] >= self.last_raise
+    }
+
+    // the largest street total the seat may bet or raise to, before looking at its stack
+    pub fn limit_total(&self, seat: usize) -> i64 {
+        match self.limit {
+            BettingLimit::NoLimit => i64::MAX,
+            BettingLimit::PotLimit => {
+                // call first, then raise by the size of the pot that makes
+                let to_call = (self.current_bet - self.committed[seat]).max(0);
+                let pot = self.invested.iter().sum::<i64>() + to_call;
+                self.current_bet + pot
+            }
+            // a short bring-in or blind is completed to a full bet first
+            BettingLimit::FixedLimit if self.current_bet < self.min_bet => self.min_bet,
+            BettingLimit::FixedLimit => self.current_bet + self.min_bet,
+        }
+    }
+
+    // the cap is lifted once only two players are left, they may raise each other all-in
+    fn capped(&self) -> bool {
+        self.limit == BettingLimit::FixedLimit
+            && self.raises >= self.raise_cap
+            && self.players_in_hand() > 2
+    }
+
+    // stud bring-in: opens the betting below a full bet, which anyone can complete to min_bet.
+    // unlike a big blind the bring-in gets no option when everybody just calls
+    pub fn post_bring_in(&mut self, seat: usize, amount: i64) -> i64 {
+        let posted = self.post_blind(seat, amount);
+        self.acted[seat] = true;
+        self.last_raise = (self.min_bet - self.current_bet).max(1);
+        posted
+    }
+
+    fn opponents_can_act(&self, seat: usize) -> bool {
+        (0..self.stacks.len()).any(|i| i != seat && self.can_act(i))
+    }
+
+    pub fn legal_actions(&self, seat: usize) -> Vec<LegalAction> {
+        let mut out = Vec::new();
+        if self.to_act != Some(seat) || !self.can_act(seat) {
+            return out;
+        }
+        let stack = self.stacks[seat];
+        let to_call = self.to_call(seat);
+        let all_in_total = self.committed[seat] + stack;
+        let max_total = all_in_total.min(self.limit_total(seat));
+
+        out.push(LegalAction::Fold);
+        if to_call == 0 {
+            out.push(LegalAction::Check);
+        } else {
+            out.push(LegalAction::Call { amount: to_call });
+        }
+
+        let can_raise = stack > to_call
+            && self.raise_reopened(seat)
+            && self.opponents_can_act(seat)
+            && !self.capped();
+        if can_raise {
+            if self.current_bet == 0 {
+                out.push(LegalAction::Bet {
+                    min: self.min_bet.min(max_total),
+                    max: max_total,
+                });
+            } else {
+                out.push(LegalAction::Raise {
+                    min: (self.current_bet + self.last_raise).min(max_total),
+                    max: max_total,
+                });
+            }
+        }
+        if (can_raise && max_total == all_in_total) || stack <= to_call {
+            out.push(LegalAction::AllIn { amount: stack });
+        }
+        out
+    }
+
+    pub fn apply(
+        &mut self,
+        seat: usize,
+        action: PlayerAction,
+    ) -> Result<AppliedAction, BettingError> {
+        if self.to_act.is_none() {
+            return Err(BettingError::RoundClosed);
+        }
+        if self.to_act != Some(seat) {
+            return Err(BettingError::NotYourTurn);
+        }
+        if !self.can_act(seat) {
+            return Err(BettingError::PlayerNotActive);
+        }
+
+        let to_call = self.to_call(seat);
+        let max_total = self.committed[seat] + self.stacks[seat];
+        let action = match action {
+            PlayerAction::AllIn if self.stacks[seat] <= to_call => PlayerAction::Call,
+            PlayerAction::AllIn if self.current_bet == 0 => PlayerAction::Bet(max_total),
+            PlayerAction::AllIn => PlayerAction::Raise(max_total),
+            other => other,
+        };
+
+        let applied = match action {
+            PlayerAction::Fold => {
+                self.in_hand[seat] = false;
+                AppliedAction {
+                    action,
+                    chips_added: 0,
+                    all_in: false,
                 }
             }
+            PlayerAction::Check => {
+                if to_call > 0 {
+                    return Err(BettingError::CannotCheck { to_call });
+                }
+                AppliedAction {
+                    action,
+                    chips_added: 0,
+                    all_in: false,
+                }
+            }
+            PlayerAction::Call => {
+                if to_call == 0 {
+                    return Err(BettingError::NothingToCall);
+                }
+                self.commit(seat, to_call);
+                AppliedAction {
+                    action,
+                    chips_added: to_call,
+                    all_in: self.stacks[seat] == 0,
+                }
+            }
+            PlayerAction::Bet(total) | PlayerAction::Raise(total) => {
+                let is_bet = matches!(action, PlayerAction::Bet(_));
+                if is_bet && self.current_bet > 0 {
+                    return Err(BettingError::BetFacingBet);
+                }
+                if !is_bet && self.current_bet == 0 {
+                    return Err(BettingError::NoBetToRaise);
+                }
+                if !self.raise_reopened(seat) || !self.opponents_can_act(seat) {
+                    return Err(BettingError::RaiseNotAllowed);
+                }
+                if self.capped() {
+                    return Err(BettingError::RaiseCapReached {
+                        cap: self.raise_cap,
+                    });
+                }
+                if total > max_total {
+                    return Err(BettingError::ExceedsStack {
+                        max: max_total,
+                        amount: total,
+                    });
+                }
+                let limit_total = self.limit_total(seat);
+                if total > limit_total {
+                    return Err(BettingError::ExceedsLimit {
+                        max: limit_total,
+                        amount: total,
+                    });
+                }
+                let min_total = if is_bet {
+                    self.min_bet
+                } else {
+                    self.current_bet + self.last_raise
+                };
+                // short all-ins are always allowed, they just don't reopen the betting
+                if total < min_total && total != max_total {
+                    return Err(BettingError::BelowMinimum {
+                        min: min_total,
+                        amount: total,
+                    });
+                }
+                if total <= self.current_bet {
+                    return Err(BettingError::BelowMinimum {
+                        min: min_total,
+                        amount: total,
+                    });
+                }
+
+                // completing a bring-in counts as a full bet, anything above it as a raise on it
+                let completes = self.current_bet > 0
+                    && self.current_bet < self.min_bet
+                    && total >= self.min_bet;
+                let raise_size = if completes {
+                    (total - self.min_bet).max(self.min_bet)
+                } else {
+                    total - self.current_bet
+                };
+                if raise_size >= self.last_raise {
+                    self.last_raise = raise_size;
+                    self.raises += 1;
+                }
+                if completes {
+                    // whoever only called the bring-in may raise again
+                    self.faced.iter_mut().for_each(|f| *f = 0);
+                }
+                let added = total - self.committed[seat];
+                self.commit(seat, added);
+                self.current_bet = total;
+                AppliedAction {
+                    action,
+                    chips_added: added,
+                    all_in: self.stacks[seat] == 0,
+                }
+            }
+            PlayerAction::AllIn => unreachable!("all-in is normalized above"),
+        };
+
+        self.acted[seat] = true;
+        self.faced[seat] = self.current_bet;
+        self.to_act = if self.is_complete() {
+            None
+        } else {
+            self.next_to_act(seat)
+        };
+        Ok(applied)
+    }
+
+    // live blind, counts towards calling and sets the bet to its full size even if the poster is short
+    pub fn post_blind(&mut self, seat: usize, amount: i64) -> i64 {
+        let posted = amount.min(self.stacks[seat]);
+        self.commit(seat, posted);
+        self.current_bet = self.current_bet.max(amount);
+        if self.current_bet >= self.min_bet {
+            self.raises = 1;
+        }
+        posted
+    }
+
+    // antes and dead blinds go to the pot without counting towards the bet
+    pub fn post_dead(&mut self, seat: usize, amount: i64) -> i64 {
+        let posted = amount.min(self.stacks[seat]);
+        self.stacks[seat] -= posted;
+        self.invested[seat] += posted;
+        posted
+    }
+
+    pub fn start_action(&mut self, first_to_act: usize) {
+        self.to_act = self.first_can_act(first_to_act);
+    }
+
+    // resets the per street commitments, stacks and folds carry over.
+    // min_bet changes when fixed limit moves up to the big bet
+    pub fn new_street(&mut self, first_to_act: usize, min_bet: i64) {
+        let n = self.stacks.len();
+        self.min_bet = min_bet;
+        self.committed = vec![0; n];
+        self.acted = vec![false; n];
+        self.faced = vec![0; n];
+        self.current_bet = 0;
+        self.last_raise = self.min_bet;
+        self.raises = 0;
+        self.to_act = self.first_can_act(first_to_act);
+    }
+
+    fn commit(&mut self, seat: usize, amount: i64) {
+        self.stacks[seat] -= amount;
+        self.committed[seat] += amount;
+        self.invested[seat] += amount;
+    }
+
+    pub fn is_complete(&self) -> bool {
+        if self.players_in_hand() <= 1 {
+            return true;
+        }
+        let actors = (0..self.stacks.len())
+            .filter(|&i| self.can_act(i))
+            .collect::<Vec<_>>();
+        // nobody left to bet against
+        if actors.len() <= 1
+            && actors
+                .iter()
+                .all(|&i| self.committed[i] >= self.current_bet)
+        {
+            return true;
+        }
+        actors
+            .iter()
+            .all(|&i| self.acted[i] && self.committed[i] == self.current_bet)
+    }
+
+    fn first_can_act(&self, from: usize) -> Option<usize> {
+        let n = self.stacks.len();
+        if n == 0 || self.is_complete() {
+            return None;
+        }
+        (0..n).map(|i| (from + i) % n).find(|&i| self.can_act(i))
+    }
+
+    fn next_to_act(&self, from: usize) -> Option<usize> {
+        self.first_can_act((from + 1) % self.stacks.len().max(1))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pot {
+    pub amount: i64,
+    pub eligible: Vec<usize>, // seats that can win this pot
+}
+
+// splits what every seat put in into a main pot and side pots, one per all-in level
+// chips of folded players stay in the pots but they are never eligible
+pub fn build_pots(invested: &[i64], in_hand: &[bool]) -> Vec<Pot> {
+    let mut levels = invested
+        .iter()
+        .zip(in_hand)
+        .filter(|&(&c, &live)| live && c > 0)
+        .map(|(&c, _)| c)
+        .collect::<Vec<_>>();
+    levels.sort_unstable();
+    levels.dedup();
+
+    let mut pots: Vec<Pot> = Vec::new();
+    let mut prev = 0;
+    for &level in &levels {
+        let amount = invested
+            .iter()
+            .map(|&c| c.min(level) - c.min(prev))
+            .sum::<i64>();
+        let eligible = (0..invested.len())
+            .filter(|&i| in_hand[i] && invested[i] >= level)
+            .collect::<Vec<_>>();
+        pots.push(Pot { amount, eligible });
+        prev = level;
+    }
+
+    // folded players can have put in more than anyone still live
+    let leftover = invested.iter().map(|&c| (c - prev).max(0)).sum::<i64>();
+    if leftover > 0 {
+        match pots.last_mut() {
+            Some(last) => last.amount += leftover,
+            None => pots.push(Pot {
+                amount: leftover,
+                eligible: Vec::new(),
+            }),
+        }
+    }
+    pots
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PotAward {
+    pub amount: i64,
+    pub eligible: Vec<usize>,
+    pub winners: Vec<(usize, i64)>, // (seat, chips won), the high half in split games
+    pub low_winners: Vec<(usize, i64)>, // empty unless a qualifying low took half of a split pot
+}
+
+// eligible seats holding the best strength, in odd chip order (closest left of the button first)
+fn best_seats<T: Ord>(eligible: &[usize], strengths: &[Option<T>], button: usize) -> Vec<usize> {
+    let n = strengths.len();
+    let best = eligible.iter().filter_map(|&i| strengths[i].as_ref()).max();
+    let mut winners = eligible
+        .iter()
+        .copied()
+        .filter(|&i| strengths[i].as_ref() == best)
+        .collect::<Vec<_>>();
+    winners.sort_by_key(|&i| (i + n - (button + 1) % n) % n);
+    winners
+}
+
+// awards every pot to the eligible seats holding the best strength, ties split evenly
+// and odd chips go one by one to the winners closest to the left of the button
+pub fn award_pots<T: Ord>(pots: &[Pot], strengths: &[Option<T>], button: usize) -> Vec<PotAward> {
+    pots.iter()
+        .map(|pot| PotAward {
+            amount: pot.amount,
+            eligible: pot.eligible.clone(),
+            winners: split_amount(pot.amount, &best_seats(&pot.eligible, strengths, button)),
+            low_winners: Vec::new(),
+        })
+        .collect()
+}
+
+// split games: half of each pot to the best high and half to the best qualifying low, the odd
+// chip staying with the high. without a qualifying low the high scoops the whole pot, and
+// tied lows share their half, which quarters the pot between two lows
+pub fn award_pots_hi_lo<H: Ord, L: Ord>(
+    pots: &[Pot],
+    highs: &[Option<H>],
+    lows: &[Option<L>],
+    button: usize,
+) -> Vec<PotAward> {
+    pots.iter()
+        .map(|pot| {
+            let high = best_seats(&pot.eligible, highs, button);
+            let has_low = pot.eligible.iter().any(|&i| lows[i].is_some());
+            let (high_share, low_winners) = if has_low {
+                let low_half = pot.amount / 2;
+                let low = best_seats(&pot.eligible, lows, button);
+                (pot.amount - low_half, split_amount(low_half, &low))
+            } else {
+                (pot.amount, Vec::new())
+            };
+            PotAward {
+                amount: pot.amount,
+                eligible: pot.eligible.clone(),
+                winners: split_amount(high_share, &high),
+                low_winners,
+            }
+        })
+        .collect()
+}
+
+// winners must already be in odd chip order
+pub fn split_amount(amount: i64, winners: &[usize]) -> Vec<(usize, i64)> {
+    if winners.is_empty() {
+        return Vec::new();
+    }
+    let share = amount / winners.len() as i64;
+    let odd = amount % winners.len() as i64;
+    winners
+        .iter()
+        .enumerate()
+        .map(|(k, &seat)| (seat, share + if (k as i64) < odd { 1 } else { 0 }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pot(amount: i64, eligible: &[usize]) -> Pot {
+        Pot {
+            amount,
+            eligible: eligible.to_vec(),
         }
     }
-    best.expect("at least one five card hand")
+
+    #[test]
+    fn one_pot_without_all_ins() {
+        let pots = build_pots(&[100, 100, 100], &[true, true, true]);
+        assert_eq!(pots, vec![pot(300, &[0, 1, 2])]);
+    }
+
+    #[test]
+    fn a_side_pot_per_all_in_level() {
+        let pots = build_pots(&[50, 200, 500, 500], &[true, true, true, true]);
+        assert_eq!(
+            pots,
+            vec![
+                pot(200, &[0, 1, 2, 3]),
+                pot(450, &[1, 2, 3]),
+                pot(600, &[2, 3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn folded_chips_stay_in_the_pots() {
+        let pots = build_pots(&[100, 300, 300, 40], &[true, false, true, false]);
+        assert_eq!(pots, vec![pot(340, &[0, 2]), pot(400, &[2])]);
+        // more than anyone still live put in, the excess goes to the last pot
+        let pots = build_pots(&[500, 100, 100], &[false, true, true]);
+        assert_eq!(pots, vec![pot(700, &[1, 2])]);
+    }
+
+    #[test]
+    fn each_pot_goes_to_its_best_eligible_hand() {
+        let pots = build_pots(&[50, 200, 200], &[true, true, true]);
+        // the short stack holds the best hand and only wins what it could match
+        let awards = award_pots(&pots, &[Some(3), Some(2), Some(1)], 0);
+        assert_eq!(awards[0].winners, vec![(0, 150)]);
+        assert_eq!(awards[1].winners, vec![(1, 300)]);
+    }
+
+    #[test]
+    fn odd_chips_go_left_of_the_button() {
+        let pots = vec![pot(101, &[0, 1, 2])];
+        let awards = award_pots(&pots, &[Some(1), Some(1), Some(1)], 1);
+        assert_eq!(awards[0].winners, vec![(2, 34), (0, 34), (1, 33)]);
+    }
+
+    #[test]
+    fn hi_lo_splits_and_scoops() {
+        let pots = vec![pot(101, &[0, 1])];
+        let split = award_pots_hi_lo(&pots, &[Some(2), Some(1)], &[None, Some(1)], 0);
+        assert_eq!(split[0].winners, vec![(0, 51)]);
+        assert_eq!(split[0].low_winners, vec![(1, 50)]);
+        let scoop = award_pots_hi_lo(&pots, &[Some(2), Some(1)], &[None::<u8>, None], 0);
+        assert_eq!(scoop[0].winners, vec![(0, 101)]);
+        assert!(scoop[0].low_winners.is_empty());
+    }
+
+    // heads-up, seat 0 posts the small blind and seat 1 the big blind
+    fn blinds(limit: BettingLimit, stacks: Vec<i64>) -> BettingRound {
+        let n = stacks.len();
+        let mut round = BettingRound::new(stacks, vec![true; n], 10, limit, 4, 0);
+        round.post_blind(0, 5);
+        round.post_blind(1, 10);
+        round.start_action(0);
+        round
+    }
+
+    fn raise_range(round: &BettingRound, seat: usize) -> Option<(i64, i64)> {
+        round.legal_actions(seat).into_iter().find_map(|a| match a {
+            LegalAction::Bet { min, max } | LegalAction::Raise { min, max } => Some((min, max)),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn no_limit_raises_at_least_the_last_raise() {
+        let mut round = blinds(BettingLimit::NoLimit, vec![1000, 1000]);
+        assert_eq!(raise_range(&round, 0), Some((20, 1000)));
+        assert_eq!(
+            round.apply(0, PlayerAction::Raise(15)),
+            Err(BettingError::BelowMinimum {
+                min: 20,
+                amount: 15
+            })
+        );
+        round.apply(0, PlayerAction::Raise(40)).unwrap();
+        // the raise was 30, so the re-raise is to at least 70
+        assert_eq!(raise_range(&round, 1), Some((70, 1000)));
+    }
+
+    #[test]
+    fn a_short_all_in_does_not_reopen_the_betting() {
+        let mut round = BettingRound::new(
+            vec![1000, 150, 1000],
+            vec![true; 3],
+            10,
+            BettingLimit::NoLimit,
+            4,
+            0,
+        );
+        round.apply(0, PlayerAction::Bet(100)).unwrap();
+        let all_in = round.apply(1, PlayerAction::AllIn).unwrap();
+        assert_eq!(all_in.action, PlayerAction::Raise(150));
+        // a full raise is still open to whoever hasn't acted
+        assert_eq!(raise_range(&round, 2), Some((250, 1000)));
+        round.apply(2, PlayerAction::Call).unwrap();
+        assert_eq!(raise_range(&round, 0), None);
+        assert_eq!(
+            round.apply(0, PlayerAction::Raise(300)),
+            Err(BettingError::RaiseNotAllowed)
+        );
+        round.apply(0, PlayerAction::Call).unwrap();
+        assert!(round.is_complete());
+        assert_eq!(round.invested, vec![150, 150, 150]);
+    }
+
+    #[test]
+    fn completing_the_bring_in_is_a_full_bet() {
+        let mut round = BettingRound::new(
+            vec![1000; 3],
+            vec![true; 3],
+            10,
+            BettingLimit::FixedLimit,
+            4,
+            0,
+        );
+        round.post_bring_in(0, 3);
+        round.start_action(1);
+        assert_eq!(raise_range(&round, 1), Some((10, 10)));
+        round.apply(1, PlayerAction::Raise(10)).unwrap();
+        assert_eq!(raise_range(&round, 2), Some((20, 20)));
+    }
+
+    #[test]
+    fn pot_limit_raises_at_most_the_pot() {
+        let mut round = blinds(BettingLimit::PotLimit, vec![1000, 1000]);
+        // calling 5 makes the pot 20, raised on top of the 10 bet
+        assert_eq!(raise_range(&round, 0), Some((20, 30)));
+        assert_eq!(
+            round.apply(0, PlayerAction::Raise(31)),
+            Err(BettingError::ExceedsLimit {
+                max: 30,
+                amount: 31
+            })
+        );
+        round.apply(0, PlayerAction::Raise(30)).unwrap();
+        // 20 to call makes the pot 60
+        assert_eq!(raise_range(&round, 1), Some((50, 90)));
+    }
+
+    #[test]
+    fn fixed_limit_caps_the_raises() {
+        let mut round = BettingRound::new(
+            vec![1000; 3],
+            vec![true; 3],
+            10,
+            BettingLimit::FixedLimit,
+            4,
+            0,
+        );
+        round.post_blind(0, 5);
+        round.post_blind(1, 10);
+        round.start_action(2);
+        // bets come in exactly one big blind at a time
+        assert_eq!(raise_range(&round, 2), Some((20, 20)));
+        round.apply(2, PlayerAction::Raise(20)).unwrap();
+        round.apply(0, PlayerAction::Raise(30)).unwrap();
+        round.apply(1, PlayerAction::Raise(40)).unwrap();
+        // the big blind counts as the first of the four
+        assert_eq!(raise_range(&round, 2), None);
+        assert_eq!(
+            round.apply(2, PlayerAction::Raise(50)),
+            Err(BettingError::RaiseCapReached { cap: 4 })
+        );
+        round.apply(2, PlayerAction::Fold).unwrap();
+        // heads-up the cap is lifted
+        assert_eq!(raise_range(&round, 0), Some((50, 50)));
+    }
 }
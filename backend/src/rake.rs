@@ -0,0 +1,128 @@
+use crate::poker_engine::Pot;
+
+pub const MAX_RAKE_BPS: i64 = 1000; // 10%
+
+// what the house takes out of a hand, set per room. the default takes nothing
+#[derive(Debug, Clone, Default)]
+pub struct RakePolicy {
+    pub bps: i64, // of the contested chips, 500 is 5%
+    // most per hand, heads-up first then one more player each, the last covers bigger hands
+    pub caps: Vec<i64>,
+    pub no_flop_no_drop: bool, // hands that end in the first betting round aren't raked
+}
+
+impl RakePolicy {
+    // None without caps
+    pub fn cap(&self, players: usize) -> Option<i64> {
+        let last = self.caps.len().checked_sub(1)?;
+        Some(self.caps[players.saturating_sub(2).min(last)])
+    }
+
+    // the rake of a hand dealt to `players`. the uncalled part of the biggest bet goes back to
+    // whoever made it and is never raked, rounding is in the players' favour
+    pub fn rake(&self, invested: &[i64], players: usize, saw_flop: bool) -> i64 {
+        if self.bps <= 0 || (self.no_flop_no_drop && !saw_flop) {
+            return 0;
+        }
+        let mut bets = invested.to_vec();
+        bets.sort_unstable_by(|a, b| b.cmp(a));
+        let uncalled = bets.first().copied().unwrap_or(0) - bets.get(1).copied().unwrap_or(0);
+        let contested = bets.iter().sum::<i64>() - uncalled;
+        let rake = contested * self.bps.min(MAX_RAKE_BPS) / 10_000;
+        self.cap(players).map_or(rake, |cap| rake.min(cap))
+    }
+
+    // takes the hand's rake off the pots before they are awarded, the main pot first.
+    // returns what each pot gave
+    pub fn take(
+        &self,
+        pots: &mut [Pot],
+        invested: &[i64],
+        players: usize,
+        saw_flop: bool,
+    ) -> Vec<i64> {
+        let mut rake = self.rake(invested, players, saw_flop);
+        pots.iter_mut()
+            .map(|pot| {
+                let taken = rake.min(pot.amount);
+                pot.amount -= taken;
+                rake -= taken;
+                taken
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poker_engine::build_pots;
+
+    fn policy(bps: i64, caps: &[i64]) -> RakePolicy {
+        RakePolicy {
+            bps,
+            caps: caps.to_vec(),
+            no_flop_no_drop: true,
+        }
+    }
+
+    #[test]
+    fn nothing_by_default() {
+        assert_eq!(RakePolicy::default().rake(&[500, 500], 2, true), 0);
+    }
+
+    #[test]
+    fn caps_by_players_dealt() {
+        let rake = policy(500, &[10, 20, 30]);
+        assert_eq!(rake.cap(2), Some(10));
+        assert_eq!(rake.cap(3), Some(20));
+        // the last cap covers every bigger table
+        assert_eq!(rake.cap(9), Some(30));
+        assert_eq!(policy(500, &[]).cap(2), None);
+        assert_eq!(rake.rake(&[1000, 1000], 2, true), 10);
+        assert_eq!(rake.rake(&[1000, 1000, 1000], 3, true), 20);
+        assert_eq!(policy(500, &[]).rake(&[1000, 1000], 2, true), 100);
+    }
+
+    #[test]
+    fn no_flop_no_drop() {
+        let rake = policy(500, &[]);
+        assert_eq!(rake.rake(&[100, 100], 2, false), 0);
+        let always = RakePolicy {
+            no_flop_no_drop: false,
+            ..rake
+        };
+        assert_eq!(always.rake(&[100, 100], 2, false), 10);
+    }
+
+    #[test]
+    fn uncalled_chips_are_not_raked() {
+        // 400 of the 500 bet is returned, 100 + 100 was contested
+        assert_eq!(policy(500, &[]).rake(&[500, 100], 2, true), 10);
+    }
+
+    #[test]
+    fn rounds_down_and_stops_at_the_maximum() {
+        assert_eq!(policy(500, &[]).rake(&[39, 39], 2, true), 3);
+        assert_eq!(policy(5000, &[]).rake(&[100, 100], 2, true), 20);
+    }
+
+    #[test]
+    fn taken_from_the_main_pot_first() {
+        let invested = [50, 300, 300];
+        let mut pots = build_pots(&invested, &[true, true, true]);
+        let taken = policy(1000, &[]).take(&mut pots, &invested, 3, true);
+        assert_eq!(taken, vec![65, 0]);
+        assert_eq!(pots[0].amount, 85);
+        assert_eq!(pots[1].amount, 500);
+        // a small main pot spills over into the side pot
+        let invested = [10, 300, 300];
+        let mut pots = build_pots(&invested, &[true, true, true]);
+        let taken = policy(1000, &[]).take(&mut pots, &invested, 3, true);
+        assert_eq!(taken, vec![30, 31]);
+        assert_eq!(
+            taken.iter().sum::<i64>() + pots[0].amount + pots[1].amount,
+            610
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use std::{collections::HashMap, str::FromStr};
+
+use thiserror::Error;
+
+use crate::{
+    notation::{parse_cards, parse_rank},
+    poker_engine::{Card, Suit},
+};
+
+// standard range syntax, comma separated:
+//   "QQ"  "AKs"  "AKo"  "AK"       one hand class (AK is suited and offsuit)
+//   "QQ+"  "ATs+"                  pairs up to aces, kicker up to one below the top card
+//   "QQ-99"  "KTs-K7s"  "76s-54s"  spans with the same top card or the same gap
+//   "AhKd"                         one exact combo
+//   "AKs:0.5"                      any of the above at a weight in (0, 1]
+// a hand listed twice keeps the weight it was given last
+
+const SUITS: [Suit; 4] = [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades];
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RangeParseError {
+    #[error("empty range")]
+    Empty,
+    #[error("invalid hand {0:?}")]
+    InvalidHand(String),
+    #[error("invalid weight in {0:?}, expected a number in (0, 1]")]
+    InvalidWeight(String),
+    #[error("invalid span {0:?}, both ends need the same top card or the same gap")]
+    InvalidSpan(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Combo {
+    pub cards: [Card; 2], // higher card first
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Range {
+    combos: Vec<Combo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Suitedness {
+    Suited,
+    Offsuit,
+    Any,
+}
+
+// a hand class like "AKs" or "77", high rank first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HandClass {
+    high: u8,
+    low: u8,
+    suited: Suitedness,
+}
+
+impl Range {
+    // every one of the 1326 starting hands, for a player whose cards are unknown
+    pub fn any() -> Self {
+        let mut range = Range::default();
+        for high in 2..=14 {
+            for low in 2..=high {
+                range.add_class(
+                    HandClass {
+                        high,
+                        low,
+                        suited: Suitedness::Any,
+                    },
+                    1.0,
+                );
+            }
+        }
+        range
+    }
+
+    pub fn from_cards(cards: [Card; 2]) -> Self {
+        Range {
+            combos: vec![Combo {
+                cards: ordered(cards),
+                weight: 1.0,
+            }],
+        }
+    }
+
+    pub fn combos(&self) -> &[Combo] {
+        &self.combos
+    }
+
+    pub fn len(&self) -> usize {
+        self.combos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.combos.is_empty()
+    }
+
+    // the number of combos counting each at its weight
+    pub fn weight(&self) -> f64 {
+        self.combos.iter().map(|c| c.weight).sum()
+    }
+
+    // drops every combo holding one of the known cards (board, dead or another player's hand)
+    pub fn remove_blocked(&mut self, known: &[Card]) {
+        self.combos
+            .retain(|c| !c.cards.iter().any(|card| known.contains(card)));
+    }
+
+    fn add_class(&mut self, class: HandClass, weight: f64) {
+        for (i, &a) in SUITS.iter().enumerate() {
+            for (j, &b) in SUITS.iter().enumerate() {
+                let keep = if class.high == class.low {
+                    i > j
+                } else {
+                    match class.suited {
+                        Suitedness::Suited => i == j,
+                        Suitedness::Offsuit => i != j,
+                        Suitedness::Any => true,
+                    }
+                };
+                if keep {
+                    self.combos.push(Combo {
+                        cards: [
+                            Card {
+                                rank: class.high,
+                                suit: a,
+                            },
+                            Card {
+                                rank: class.low,
+                                suit: b,
+                            },
+                        ],
+                        weight,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn ordered([a, b]: [Card; 2]) -> [Card; 2] {
+    if (a.rank, a.suit as u8) >= (b.rank, b.suit as u8) {
+        [a, b]
+    } else {
+        [b, a]
+    }
+}
+
+fn parse_class(s: &str) -> Option<HandClass> {
+    let mut chars = s.chars();
+    let first = parse_rank(chars.next()?)?;
+    let second = parse_rank(chars.next()?)?;
+    let suited = match chars.next() {
+        None => Suitedness::Any,
+        Some('s' | 'S') if first != second => Suitedness::Suited,
+        Some('o' | 'O') if first != second => Suitedness::Offsuit,
+        Some(_) => return None,
+    };
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(HandClass {
+        high: first.max(second),
+        low: first.min(second),
+        suited,
+    })
+}
+
+// expands one comma separated piece of a range, without its weight
+fn expand(token: &str) -> Result<Vec<HandClass>, RangeParseError> {
+    let invalid = || RangeParseError::InvalidHand(token.to_string());
+
+    if let Some(base) = token.strip_suffix('+') {
+        let class = parse_class(base).ok_or_else(invalid)?;
+        return Ok(if class.high == class.low {
+            (class.low..=14)
+                .map(|r| HandClass {
+                    high: r,
+                    low: r,
+                    ..class
+                })
+                .collect()
+        } else {
+            (class.low..class.high)
+                .map(|low| HandClass { low, ..class })
+                .collect()
+        });
+    }
+
+    if let Some((from, to)) = token.split_once('-') {
+        let a = parse_class(from.trim()).ok_or_else(invalid)?;
+        let b = parse_class(to.trim()).ok_or_else(invalid)?;
+        let (top, bottom) = if a.low >= b.low { (a, b) } else { (b, a) };
+        let span = RangeParseError::InvalidSpan(token.to_string());
+        if top.suited != bottom.suited {
+            return Err(span);
+        }
+        let pairs = top.high == top.low && bottom.high == bottom.low;
+        let same_gap = top.high - top.low == bottom.high - bottom.low;
+        return if pairs || (same_gap && top.high != top.low) {
+            Ok((0..=top.low - bottom.low)
+                .map(|step| HandClass {
+                    high: bottom.high + step,
+                    low: bottom.low + step,
+                    ..top
+                })
+                .collect())
+        } else if top.high == bottom.high && top.high != top.low && bottom.high != bottom.low {
+            Ok((bottom.low..=top.low)
+                .map(|low| HandClass { low, ..top })
+                .collect())
+        } else {
+            Err(span)
+        };
+    }
+
+    parse_class(token).map(|c| vec![c]).ok_or_else(invalid)
+}
+
+impl FromStr for Range {
+    type Err = RangeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut range = Range::default();
+        let mut index: HashMap<[Card; 2], usize> = HashMap::new();
+
+        for token in s.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let (hand, weight) = match token.split_once(':') {
+                Some((hand, weight)) => {
+                    let weight = weight
+                        .trim()
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|w| *w > 0.0 && *w <= 1.0)
+                        .ok_or_else(|| RangeParseError::InvalidWeight(token.to_string()))?;
+                    (hand.trim(), weight)
+                }
+                None => (token, 1.0),
+            };
+
+            let mut added = Range::default();
+            match parse_cards(hand).as_deref() {
+                Ok(&[a, b]) => added.combos.push(Combo {
+                    cards: ordered([a, b]),
+                    weight,
+                }),
+                _ => {
+                    for class in expand(hand)? {
+                        added.add_class(class, weight);
+                    }
+                }
+            }
+
+            for combo in added.combos {
+                match index.get(&combo.cards) {
+                    Some(&i) => range.combos[i].weight = weight,
+                    None => {
+                        index.insert(combo.cards, range.combos.len());
+                        range.combos.push(combo);
+                    }
+                }
+            }
+        }
+
+        if range.is_empty() {
+            return Err(RangeParseError::Empty);
+        }
+        Ok(range)
+    }
+}
@@ -0,0 +1,346 @@
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, web};
+use chrono::{Duration, Utc};
+use database::models::{
+    NewRoom, count_open_rooms, create_rooms, find_by_id_rooms, list_by_room, list_open_rooms,
+    rake_report as room_rake_report, update_rooms,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::auth::jwt::Claims;
+use crate::deck::{DeckSource, SecureShuffle, SeededShuffle, StackedDeck};
+use crate::errors::ServiceError;
+use crate::fairness::ServerSeed;
+use crate::game_manager::{DEFAULT_MAX_PLAYERS, GameManager, MAX_SEATS, ROOM_CLOSED};
+use crate::notation::{parse_board, parse_cards};
+use crate::poker_engine::{BettingLimit, GameVariant};
+use crate::state::AppState;
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+const DEFAULT_REPORT_DAYS: i64 = 30;
+const MAX_REPORT_DAYS: i64 = 365;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateRoomDto {
+    #[validate(length(min = 1, max = 64))]
+    pub room_name: Option<String>,
+    pub variant: Option<String>,       // holdem when left out
+    pub betting_limit: Option<String>, // the variant's usual structure when left out
+    #[validate(range(min = 1))]
+    pub small_blind: i64,
+    #[validate(range(min = 1))]
+    pub big_blind: i64,
+    #[serde(default)]
+    #[validate(range(min = 0))]
+    pub ante: i64,
+    #[validate(range(min = 2, max = 10))]
+    pub max_players: Option<i16>,
+    pub min_buy_in: Option<i64>, // 20 big blinds when left out
+    pub max_buy_in: Option<i64>, // 100 big blinds when left out
+    #[validate(range(min = 1, max = 10))]
+    pub raise_cap: Option<i16>,
+    #[validate(range(min = 5, max = 120))]
+    pub action_clock_secs: Option<i16>,
+    #[validate(range(min = 0, max = 600))]
+    pub time_bank_secs: Option<i16>,
+    #[validate(range(min = 0, max = 1000))]
+    pub time_bank_refill_hands: Option<i16>,
+    #[validate(range(min = 0, max = 1000))]
+    pub rake_bps: Option<i16>, // no rake when left out
+    #[serde(default)]
+    #[validate(length(max = 9))]
+    pub rake_caps: Vec<i64>, // heads-up first, see RakePolicy
+    pub no_flop_no_drop: Option<bool>, // on when left out
+    #[serde(default)]
+    pub debug: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRoomsQuery {
+    pub page: Option<i64>, // from 1
+    pub per_page: Option<i64>,
+}
+
+// where a debug room's cards come from, starting with the next hand. cards are written like
+// "AhKd Qc", see notation
+#[derive(Debug, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum DeckSourceDto {
+    Secure,
+    Seeded {
+        server_seed: String,
+    }, // 64 hex characters, used for every hand
+    Stacked {
+        cards: String,
+    }, // in dealing order, the rest of the deck follows
+    FlopGame {
+        hole_cards: Vec<String>,
+        board: String,
+    }, // hole cards in seat order
+}
+
+impl DeckSourceDto {
+    fn into_source(self) -> Result<Arc<dyn DeckSource>, ServiceError> {
+        let bad = |e: String| ServiceError::BadRequest(e);
+        Ok(match self {
+            DeckSourceDto::Secure => Arc::new(SecureShuffle),
+            DeckSourceDto::Seeded { server_seed } => Arc::new(SeededShuffle::new(
+                ServerSeed::from_hex(&server_seed).map_err(|e| bad(e.to_string()))?,
+            )),
+            DeckSourceDto::Stacked { cards } => Arc::new(StackedDeck::new(
+                parse_cards(&cards).map_err(|e| bad(e.to_string()))?,
+            )),
+            DeckSourceDto::FlopGame { hole_cards, board } => {
+                let hole_cards = hole_cards
+                    .iter()
+                    .map(|h| parse_cards(h))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| bad(e.to_string()))?;
+                let board = parse_board(&board).map_err(|e| bad(e.to_string()))?;
+                Arc::new(StackedDeck::flop_game(&hole_cards, &board))
+            }
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RakeReportQuery {
+    pub days: Option<i64>,
+}
+
+fn user_id(req: &HttpRequest) -> Result<Uuid, ServiceError> {
+    let claims = req
+        .extensions()
+        .get::<Claims>()
+        .cloned()
+        .ok_or(ServiceError::Unauthorized("no claims".into()))?;
+    Uuid::parse_str(&claims.sub).map_err(|_| ServiceError::BadRequest("Invalid Sub Claims".into()))
+}
+
+// checks what the validator attributes can't, and fills in the defaults
+fn new_room(dto: CreateRoomDto, host: Uuid, debug_rooms: bool) -> Result<NewRoom, ServiceError> {
+    let variant = match dto.variant.as_deref() {
+        Some(name) => GameVariant::from_name(name)
+            .ok_or_else(|| ServiceError::BadRequest(format!("unknown variant {}", name)))?,
+        None => GameVariant::default(),
+    };
+    if let Some(limit) = dto.betting_limit.as_deref()
+        && BettingLimit::from_name(limit).is_none()
+    {
+        return Err(ServiceError::BadRequest(format!(
+            "unknown betting limit {}",
+            limit
+        )));
+    }
+    if dto.small_blind > dto.big_blind {
+        return Err(ServiceError::BadRequest(
+            "the small blind can't be more than the big blind".into(),
+        ));
+    }
+    let max_players = dto.max_players.unwrap_or(DEFAULT_MAX_PLAYERS as i16);
+    let seats = variant.max_players().min(MAX_SEATS);
+    if max_players as usize > seats {
+        return Err(ServiceError::BadRequest(format!(
+            "{} deals to at most {} players",
+            variant.as_str(),
+            seats
+        )));
+    }
+    let min_buy_in = dto.min_buy_in.unwrap_or(dto.big_blind * 20);
+    let max_buy_in = dto.max_buy_in.unwrap_or(dto.big_blind * 100);
+    if min_buy_in < dto.big_blind || max_buy_in < min_buy_in {
+        return Err(ServiceError::BadRequest(
+            "buy-ins go from at least a big blind up to max_buy_in".into(),
+        ));
+    }
+    if dto.rake_caps.iter().any(|&cap| cap < 0) {
+        return Err(ServiceError::BadRequest(
+            "rake caps can't be negative".into(),
+        ));
+    }
+    if dto.debug && !debug_rooms {
+        return Err(ServiceError::Forbidden(
+            "this server doesn't allow debug rooms".into(),
+        ));
+    }
+    Ok(NewRoom {
+        room_name: dto.room_name,
+        host_user_id: Some(host),
+        max_players: Some(max_players),
+        small_blind: dto.small_blind,
+        big_blind: dto.big_blind,
+        ante: dto.ante,
+        variant: variant.as_str().to_string(),
+        betting_limit: dto.betting_limit,
+        raise_cap: dto.raise_cap.unwrap_or(4),
+        debug: dto.debug,
+        action_clock_secs: dto.action_clock_secs.unwrap_or(30),
+        time_bank_secs: dto.time_bank_secs.unwrap_or(60),
+        time_bank_refill_hands: dto.time_bank_refill_hands.unwrap_or(20),
+        min_buy_in,
+        max_buy_in,
+        rake_bps: dto.rake_bps.unwrap_or(0),
+        rake_caps: dto.rake_caps,
+        no_flop_no_drop: dto.no_flop_no_drop.unwrap_or(true),
+    })
+}
+
+pub async fn create_room(
+    state: web::Data<AppState>,
+    gm: web::Data<GameManager>,
+    req: HttpRequest,
+    payload: web::Json<CreateRoomDto>,
+) -> Result<HttpResponse, ServiceError> {
+    let host = user_id(&req)?;
+    let dto = payload.into_inner();
+    dto.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    let room = new_room(dto, host, state.setting.debug_rooms)?;
+    let room_id = create_rooms(&state.pool, &room).await?;
+    let room = find_by_id_rooms(&state.pool, room_id)
+        .await?
+        .ok_or(ServiceError::InternalServerError)?;
+    gm.publish_lobby(room_id);
+    Ok(HttpResponse::Created().json(room))
+}
+
+// the room with whoever is seated at it
+pub async fn get_room(
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let room_id = path.into_inner();
+    let room = find_by_id_rooms(&state.pool, room_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("room {}", room_id)))?;
+    let players = list_by_room(&state.pool, room_id).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "room": room,
+        "players": players,
+    })))
+}
+
+pub async fn list_rooms(
+    state: web::Data<AppState>,
+    query: web::Query<ListRoomsQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let rooms = list_open_rooms(&state.pool, per_page, (page - 1) * per_page).await?;
+    let total = count_open_rooms(&state.pool).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "rooms": rooms,
+        "page": page,
+        "per_page": per_page,
+        "total": total,
+    })))
+}
+
+// only the host closes a table, and only between hands
+pub async fn close_room(
+    state: web::Data<AppState>,
+    gm: web::Data<GameManager>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let user_id = user_id(&req)?;
+    let room_id = path.into_inner();
+    let room = find_by_id_rooms(&state.pool, room_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("room {}", room_id)))?;
+    if room.host_user_id != Some(user_id) {
+        return Err(ServiceError::Forbidden(
+            "only the host can close the room".into(),
+        ));
+    }
+    if room.room_status == ROOM_CLOSED {
+        return Err(ServiceError::Conflict("room is already closed".into()));
+    }
+    // closed first so nobody can join while the seats are cashed out
+    update_rooms(&state.pool, room_id, ROOM_CLOSED).await?;
+    if let Err(e) = gm.close_room(room_id).await {
+        update_rooms(&state.pool, room_id, &room.room_status).await?;
+        return Err(ServiceError::Conflict(e.to_string()));
+    }
+    gm.publish_lobby(room_id);
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "room_id": room_id,
+        "room_status": ROOM_CLOSED,
+    })))
+}
+
+// what the room paid the house per day, for its host
+pub async fn rake_report(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    query: web::Query<RakeReportQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    let user_id = user_id(&req)?;
+    let room_id = path.into_inner();
+    let room = find_by_id_rooms(&state.pool, room_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("room {}", room_id)))?;
+    if room.host_user_id != Some(user_id) {
+        return Err(ServiceError::Forbidden(
+            "only the host can see the rake".into(),
+        ));
+    }
+    let days = query
+        .days
+        .unwrap_or(DEFAULT_REPORT_DAYS)
+        .clamp(1, MAX_REPORT_DAYS);
+    let report = room_rake_report(&state.pool, room_id, Utc::now() - Duration::days(days)).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "room_id": room_id,
+        "rake_bps": room.rake_bps,
+        "rake_caps": room.rake_caps,
+        "no_flop_no_drop": room.no_flop_no_drop,
+        "days": days,
+        "hands": report.iter().map(|d| d.hands).sum::<i64>(),
+        "rake": report.iter().map(|d| d.rake).sum::<i64>(),
+        "by_day": report,
+    })))
+}
+
+// debug rooms on a server that allows them only, for the host to deal known cards
+pub async fn set_deck_source(
+    state: web::Data<AppState>,
+    gm: web::Data<GameManager>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    payload: web::Json<DeckSourceDto>,
+) -> Result<HttpResponse, ServiceError> {
+    let user_id = user_id(&req)?;
+    let room_id = path.into_inner();
+    if !state.setting.debug_rooms {
+        return Err(ServiceError::Forbidden(
+            "this server doesn't allow debug rooms".into(),
+        ));
+    }
+    let room = find_by_id_rooms(&state.pool, room_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("room {}", room_id)))?;
+    if room.host_user_id != Some(user_id) {
+        return Err(ServiceError::Forbidden(
+            "only the host can set the deck".into(),
+        ));
+    }
+    let source = payload.into_inner().into_source()?;
+    let name = source.name();
+    let next_seed_hash = gm
+        .set_deck_source(room_id, source)
+        .await
+        .map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "room_id": room_id,
+        "deck_source": name,
+        "next_seed_hash": next_seed_hash,
+    })))
+}
@@ -0,0 +1,19 @@
+pub mod handlers;
+
+use actix_web::web;
+
+use crate::rooms::handlers::{
+    close_room, create_room, get_room, list_rooms, rake_report, set_deck_source,
+};
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("")
+            .route(web::post().to(create_room))
+            .route(web::get().to(list_rooms)),
+    );
+    cfg.service(web::resource("/{room_id}").route(web::get().to(get_room)));
+    cfg.service(web::resource("/{room_id}/close").route(web::post().to(close_room)));
+    cfg.service(web::resource("/{room_id}/rake").route(web::get().to(rake_report)));
+    cfg.service(web::resource("/{room_id}/deck").route(web::post().to(set_deck_source)));
+}
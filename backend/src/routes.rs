@@ -4,6 +4,11 @@ use actix_web::{
 };
 
 use crate::auth::{handlers::me, init_routes as auth_routes, middleware::AuthMiddleware};
+use crate::hands::init_routes as hands_routes;
+use crate::lobby::init_routes as lobby_routes;
+use crate::rooms::init_routes as rooms_routes;
+use crate::tools::init_routes as tools_routes;
+use crate::ws_server::ws_session;
 
 pub fn init_routes(cfg: &mut ServiceConfig) {
     cfg.service(
@@ -12,11 +17,17 @@ pub fn init_routes(cfg: &mut ServiceConfig) {
                 "/health",
                 web::get().to(|| async { HttpResponse::Ok().body("ok") }),
             )
+            // authenticates itself, the upgrade request may carry the token as ?token=
+            .route("/ws", web::get().to(ws_session))
             .service(web::scope("/auth").configure(auth_routes))
+            .service(web::scope("/hands").configure(hands_routes))
             .service(
                 web::scope("/proc")
                     .wrap(AuthMiddleware::new())
-                    .route("/me", web::get().to(me)),
+                    .route("/me", web::get().to(me))
+                    .service(web::scope("/lobby").configure(lobby_routes))
+                    .service(web::scope("/rooms").configure(rooms_routes))
+                    .service(web::scope("/tools").configure(tools_routes)),
             ),
     );
 }
@@ -0,0 +1,138 @@
+use actix_web::{HttpResponse, web};
+use serde::Deserialize;
+
+use crate::equity::{self, DEFAULT_SAMPLES};
+use crate::errors::ServiceError;
+use crate::notation::{format_cards, parse_board, parse_cards};
+use crate::poker_engine::Card;
+use crate::range::Range;
+
+#[derive(Debug, Deserialize)]
+pub struct EquityDto {
+    pub hands: Vec<Option<String>>, // "AhKd", or null / "" for a player with unknown cards
+    #[serde(default)]
+    pub board: String,
+    #[serde(default)]
+    pub dead: String,
+    pub samples: Option<u64>,
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RangeEquityDto {
+    pub ranges: Vec<String>, // "QQ+, AKs, ATs+:0.5"
+    #[serde(default)]
+    pub board: String,
+    #[serde(default)]
+    pub dead: String,
+    pub samples: Option<u64>,
+    pub seed: Option<u64>,
+}
+
+fn parse_hand(hand: Option<&str>) -> Result<Option<[Card; 2]>, ServiceError> {
+    let Some(hand) = hand.map(str::trim).filter(|h| !h.is_empty()) else {
+        return Ok(None);
+    };
+    let cards = parse_cards(hand).map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+    match cards[..] {
+        [a, b] => Ok(Some([a, b])),
+        _ => Err(ServiceError::BadRequest(format!(
+            "a hand has 2 cards, got {}",
+            cards.len()
+        ))),
+    }
+}
+
+pub async fn calculate_equity(payload: web::Json<EquityDto>) -> Result<HttpResponse, ServiceError> {
+    let payload = payload.into_inner();
+    let hands = payload
+        .hands
+        .iter()
+        .map(|h| parse_hand(h.as_deref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let board = parse_board(&payload.board).map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+    let dead = parse_cards(&payload.dead).map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+    let samples = payload.samples.unwrap_or(DEFAULT_SAMPLES);
+
+    // enumeration can take a while, keep it off the async workers
+    let (hands, result) = web::block(move || {
+        let result = equity::calculate(&hands, &board, &dead, samples, payload.seed);
+        (hands, result)
+    })
+    .await
+    .map_err(|_| ServiceError::InternalServerError)?;
+    let result = result.map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+
+    let players: Vec<serde_json::Value> = hands
+        .iter()
+        .zip(&result.players)
+        .map(|(hand, equity)| {
+            serde_json::json!({
+                "hand": hand.map(|h| format_cards(&h)),
+                "win": equity.win,
+                "tie": equity.tie,
+                "lose": equity.lose,
+                "equity": equity.equity,
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "players": players,
+        "exact": result.exact,
+        "runouts": result.runouts,
+        "seed": result.seed,
+    })))
+}
+
+pub async fn calculate_range_equity(
+    payload: web::Json<RangeEquityDto>,
+) -> Result<HttpResponse, ServiceError> {
+    let payload = payload.into_inner();
+    let mut ranges = payload
+        .ranges
+        .iter()
+        .map(|r| r.parse::<Range>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+    let board = parse_board(&payload.board).map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+    let dead = parse_cards(&payload.dead).map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+    let samples = payload.samples.unwrap_or(DEFAULT_SAMPLES);
+    let blocked: Vec<Card> = board.iter().chain(&dead).copied().collect();
+    for range in &mut ranges {
+        range.remove_blocked(&blocked);
+    }
+
+    let (ranges, result) = web::block(move || {
+        let result = equity::calculate_ranges(&ranges, &board, &dead, samples, payload.seed);
+        (ranges, result)
+    })
+    .await
+    .map_err(|_| ServiceError::InternalServerError)?;
+    let result = result.map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+
+    let players: Vec<serde_json::Value> = payload
+        .ranges
+        .iter()
+        .zip(&ranges)
+        .zip(&result.players)
+        .map(|((text, range), equity)| {
+            serde_json::json!({
+                "range": text,
+                "combos": range.len(),
+                "weighted_combos": range.weight(),
+                "win": equity.win,
+                "tie": equity.tie,
+                "lose": equity.lose,
+                "equity": equity.equity,
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "players": players,
+        "exact": result.exact,
+        "runouts": result.runouts,
+        "seed": result.seed,
+    })))
+}
@@ -0,0 +1,10 @@
+pub mod handlers;
+
+use actix_web::web;
+
+use crate::tools::handlers::{calculate_equity, calculate_range_equity};
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/equity").route(web::post().to(calculate_equity)));
+    cfg.service(web::resource("/equity/ranges").route(web::post().to(calculate_range_equity)));
+}
@@ -0,0 +1,154 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    game_manager::{HandState, RoomState},
+    poker_engine::{Card, Street},
+};
+
+// the table with every card face up. never sent as it is, view_for turns it into what
+// one recipient is allowed to see
+#[derive(Debug, Clone, Default)]
+pub struct TableSnapshot {
+    hand: Option<HandSnapshot>,
+    seats: Vec<SeatSnapshot>,
+    next_seed_hash: String,
+}
+
+#[derive(Debug, Clone)]
+struct HandSnapshot {
+    id: Uuid,
+    street: Street,
+    pot: i64,
+    board: Vec<Card>,
+    current_turn: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct SeatSnapshot {
+    seat: usize, // 1 based like the seats in the database
+    user_id: Uuid,
+    chips: i64,
+    connected: bool,
+    sitting_out: bool,
+    in_hand: bool,
+    cards: Vec<Card>, // in the order they were dealt
+    up_cards: Vec<Card>,
+    shown: bool,
+}
+
+// what one player or spectator sees, a null card is a card back
+#[derive(Debug, Clone, Serialize)]
+pub struct TableView {
+    pub hand_id: Option<Uuid>,
+    pub street: Option<Street>,
+    pub pot: i64,
+    pub board: Vec<String>,
+    pub current_turn: Option<usize>,
+    pub seats: Vec<SeatView>,
+    pub next_seed_hash: String, // the commitment for the next hand, seeds set now go into it
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeatView {
+    pub seat: usize,
+    pub user_id: Uuid,
+    pub chips: i64,
+    pub connected: bool,
+    pub sitting_out: bool,
+    pub in_hand: bool,
+    pub cards: Vec<Option<String>>,
+    pub shown: bool,
+}
+
+impl TableSnapshot {
+    pub fn capture(r: &RoomState) -> Self {
+        Self::of_hand(r, r.active_hand.as_ref())
+    }
+
+    // for a hand already taken out of the room, like the one finish_hand is settling
+    pub fn of_hand(r: &RoomState, hs: Option<&HandState>) -> Self {
+        let seats = r
+            .seats
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| {
+                let ps = slot.as_ref()?;
+                Some(SeatSnapshot {
+                    seat: i + 1,
+                    user_id: ps.user_id,
+                    chips: ps.chips,
+                    connected: ps.connected,
+                    sitting_out: ps.sitting_out,
+                    in_hand: hs.is_some_and(|h| h.players_in_hand.get(i) == Some(&true)),
+                    cards: hs
+                        .and_then(|h| h.hole_cards.get(i).cloned().flatten())
+                        .unwrap_or_default(),
+                    up_cards: hs
+                        .and_then(|h| h.up_cards.get(i).cloned())
+                        .unwrap_or_default(),
+                    shown: false,
+                })
+            })
+            .collect();
+        TableSnapshot {
+            hand: hs.map(|h| HandSnapshot {
+                id: h.id,
+                street: h.round,
+                pot: h.pot,
+                board: h.board.clone(),
+                current_turn: h.current_turn,
+            }),
+            seats,
+            next_seed_hash: r.next_seed.hash.clone(),
+        }
+    }
+
+    // every player still in at showdown turns their cards over
+    pub fn reveal_showdown(&mut self) {
+        for seat in self.seats.iter_mut().filter(|s| s.in_hand) {
+            seat.shown = true;
+        }
+    }
+
+    // viewer None is a spectator, so is a user without a seat at this table
+    pub fn view_for(&self, viewer: Option<Uuid>) -> TableView {
+        let seats = self
+            .seats
+            .iter()
+            .map(|s| {
+                let visible = s.shown || Some(s.user_id) == viewer;
+                SeatView {
+                    seat: s.seat,
+                    user_id: s.user_id,
+                    chips: s.chips,
+                    connected: s.connected,
+                    sitting_out: s.sitting_out,
+                    in_hand: s.in_hand,
+                    cards: s
+                        .cards
+                        .iter()
+                        .map(|c| (visible || s.up_cards.contains(c)).then(|| c.to_string()))
+                        .collect(),
+                    shown: s.shown,
+                }
+            })
+            .collect();
+        TableView {
+            hand_id: self.hand.as_ref().map(|h| h.id),
+            street: self.hand.as_ref().map(|h| h.street),
+            pot: self.hand.as_ref().map(|h| h.pot).unwrap_or(0),
+            board: self
+                .hand
+                .as_ref()
+                .map(|h| h.board.iter().map(|c| c.to_string()).collect())
+                .unwrap_or_default(),
+            current_turn: self
+                .hand
+                .as_ref()
+                .and_then(|h| h.current_turn.map(|t| t + 1)),
+            seats,
+            next_seed_hash: self.next_seed_hash.clone(),
+        }
+    }
+}
@@ -1,14 +1,380 @@
+use std::collections::HashSet;
+
+use actix_web::{HttpRequest, HttpResponse, http::header::AUTHORIZATION, web};
+use actix_ws::{AggregatedMessage, AggregatedMessageStream, Session};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
+use crate::auth::jwt::validate_token;
+use crate::errors::ServiceError;
+use crate::game_manager::{GameManager, OutgoingEvent, PreAction};
+use crate::lobby::listing::{LobbyTable, open_tables};
+use crate::poker_engine::PlayerAction;
+use crate::state::AppState;
+use crate::views::TableView;
+
+const OUTGOING_BUFFER: usize = 64; // events queued for a slow socket before new ones are dropped
+const MAX_FRAME_SIZE: usize = 16 * 1024;
+const MAX_CHAT_LEN: usize = 280;
+
 #[derive(Debug, Clone)]
 pub enum Outgoing {
     Text(String),
-    Binary(Vec<u8>),
 }
 
 #[derive(Clone)]
 pub struct ClientInfo {
+    pub conn_id: Uuid, // one per socket, a user can have several open
     pub user_id: Uuid,
     pub tx: mpsc::Sender<Outgoing>,
 }
+
+// what clients send, {"type": "join_room", "room_id": "..."}
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientCommand {
+    // buy_in is taken from the balance, as much as the table allows when left out
+    JoinRoom {
+        room_id: Uuid,
+        seat: Option<u8>,
+        buy_in: Option<i64>,
+    },
+    LeaveRoom {
+        room_id: Uuid,
+    },
+    // after a reconnect, last_seq is the seq of the last game event the client saw
+    Resume {
+        room_id: Uuid,
+        last_seq: Option<u64>,
+    },
+    Act {
+        room_id: Uuid,
+        #[serde(flatten)]
+        action: PlayerAction,
+    },
+    SitOut {
+        room_id: Uuid,
+        sitting_out: bool,
+    },
+    // mixed into the shuffle of the next hand, see fairness
+    SetClientSeed {
+        room_id: Uuid,
+        seed: String,
+    },
+    // a rebuy or top-up between hands
+    AddChips {
+        room_id: Uuid,
+        amount: i64,
+    },
+    // the full lobby first, then a lobby_table or lobby_removed whenever a table changes
+    SubscribeLobby,
+    UnsubscribeLobby,
+    // null clears it
+    PreAction {
+        room_id: Uuid,
+        pre_action: Option<PreAction>,
+    },
+    Chat {
+        room_id: Uuid,
+        message: String,
+    },
+}
+
+// what the server sends, game events keep their own event_type next to "type": "game"
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    Joined {
+        room_id: Uuid,
+        seat: u8,
+    },
+    // cashed_out is null when the player is still in the hand, the stack goes back once it ends
+    Left {
+        room_id: Uuid,
+        cashed_out: Option<i64>,
+    },
+    Resumed {
+        room_id: Uuid,
+        seq: u64,
+        replayed: usize,
+    },
+    Snapshot {
+        room_id: Uuid,
+        seq: u64,
+        table: TableView,
+    },
+    SittingOut {
+        room_id: Uuid,
+        sitting_out: bool,
+    },
+    ChipsAdded {
+        room_id: Uuid,
+        chips: i64,
+    },
+    ClientSeedSet {
+        room_id: Uuid,
+        next_seed_hash: String,
+    },
+    Lobby {
+        tables: Vec<LobbyTable>,
+    },
+    LobbyTable {
+        table: LobbyTable,
+    },
+    LobbyRemoved {
+        room_id: Uuid,
+    },
+    LobbyUnsubscribed,
+    PreActionSet {
+        room_id: Uuid,
+        pre_action: Option<PreAction>,
+    },
+    Game(OutgoingEvent),
+    Error {
+        message: String,
+    },
+}
+
+impl ServerEvent {
+    pub fn to_outgoing(&self) -> Option<Outgoing> {
+        serde_json::to_string(self).ok().map(Outgoing::Text)
+    }
+}
+
+// one open socket and the rooms it is registered in
+struct Connection {
+    id: Uuid,
+    user_id: Uuid,
+    tx: mpsc::Sender<Outgoing>,
+    rooms: HashSet<Uuid>,
+}
+
+// browsers can't set headers on a websocket upgrade, so the token may also come as ?token=
+fn authenticate(req: &HttpRequest, state: &AppState) -> Result<Uuid, ServiceError> {
+    let header = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .map(str::trim)
+        .filter(|s| s.to_ascii_lowercase().starts_with("bearer "))
+        .map(|s| s[7..].trim().to_string());
+    let query =
+        web::Query::<std::collections::HashMap<String, String>>::from_query(req.query_string())
+            .ok()
+            .and_then(|q| q.get("token").cloned());
+    let token = header
+        .or(query)
+        .ok_or_else(|| ServiceError::Unauthorized("missing token".into()))?;
+    let data = validate_token(&token, &state.setting.jwt_secret)
+        .map_err(|_| ServiceError::Unauthorized("invalid token".into()))?;
+    Uuid::parse_str(&data.claims.sub)
+        .map_err(|_| ServiceError::Unauthorized("invalid sub claim".into()))
+}
+
+pub async fn ws_session(
+    req: HttpRequest,
+    body: web::Payload,
+    state: web::Data<AppState>,
+    gm: web::Data<GameManager>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = authenticate(&req, &state)?;
+    let (response, session, stream) = actix_ws::handle(&req, body)?;
+    let stream = stream
+        .max_frame_size(MAX_FRAME_SIZE)
+        .aggregate_continuations();
+
+    let (tx, rx) = mpsc::channel(OUTGOING_BUFFER);
+    let conn = Connection {
+        id: Uuid::new_v4(),
+        user_id,
+        tx,
+        rooms: HashSet::new(),
+    };
+    actix_web::rt::spawn(write_loop(session.clone(), rx));
+    actix_web::rt::spawn(read_loop(gm.get_ref().clone(), conn, session, stream));
+    Ok(response)
+}
+
+// everything sent to the socket goes through the channel, so game events and replies keep their order
+async fn write_loop(mut session: Session, mut rx: mpsc::Receiver<Outgoing>) {
+    while let Some(msg) = rx.recv().await {
+        let sent = match msg {
+            Outgoing::Text(s) => session.text(s).await,
+        };
+        if sent.is_err() {
+            break;
+        }
+    }
+}
+
+async fn read_loop(
+    gm: GameManager,
+    mut conn: Connection,
+    mut session: Session,
+    mut stream: AggregatedMessageStream,
+) {
+    while let Some(Ok(msg)) = stream.recv().await {
+        match msg {
+            AggregatedMessage::Text(text) => {
+                let reply = match serde_json::from_str::<ClientCommand>(&text) {
+                    Ok(cmd) => dispatch(&gm, &mut conn, cmd).await,
+                    Err(e) => Err(anyhow::anyhow!("invalid command: {}", e)),
+                };
+                let reply = match reply {
+                    Ok(Some(ev)) => ev,
+                    Ok(None) => continue,
+                    Err(e) => ServerEvent::Error {
+                        message: e.to_string(),
+                    },
+                };
+                if let Some(out) = reply.to_outgoing() {
+                    let _ = conn.tx.try_send(out);
+                }
+            }
+            AggregatedMessage::Ping(bytes) => {
+                if session.pong(&bytes).await.is_err() {
+                    break;
+                }
+            }
+            AggregatedMessage::Close(_) => break,
+            AggregatedMessage::Binary(_) | AggregatedMessage::Pong(_) => {}
+        }
+    }
+
+    // the seat is kept, only this socket stops getting the room's events. it shows as
+    // disconnected once the user has no other socket in the room
+    for room_id in conn.rooms.drain() {
+        unregister(&gm, room_id, conn.id);
+        let still_open = gm
+            .client_registry
+            .get(&room_id)
+            .is_some_and(|clients| clients.iter().any(|ci| ci.user_id == conn.user_id));
+        if !still_open {
+            let _ = gm.set_connected(conn.user_id, room_id, false).await;
+        }
+    }
+    gm.lobby_subscribers.remove(&conn.id);
+    let _ = session.close(None).await;
+}
+
+async fn dispatch(
+    gm: &GameManager,
+    conn: &mut Connection,
+    cmd: ClientCommand,
+) -> anyhow::Result<Option<ServerEvent>> {
+    match cmd {
+        ClientCommand::JoinRoom {
+            room_id,
+            seat,
+            buy_in,
+        } => {
+            let seat = gm.join_room(conn.user_id, room_id, seat, buy_in).await?;
+            if conn.rooms.insert(room_id) {
+                gm.client_registry
+                    .entry(room_id)
+                    .or_default()
+                    .push(ClientInfo {
+                        conn_id: conn.id,
+                        user_id: conn.user_id,
+                        tx: conn.tx.clone(),
+                    });
+            }
+            Ok(Some(ServerEvent::Joined { room_id, seat }))
+        }
+        ClientCommand::LeaveRoom { room_id } => {
+            let cashed_out = gm.leave_room(conn.user_id, room_id).await?;
+            if conn.rooms.remove(&room_id) {
+                unregister(gm, room_id, conn.id);
+            }
+            Ok(Some(ServerEvent::Left {
+                room_id,
+                cashed_out,
+            }))
+        }
+        ClientCommand::Resume { room_id, last_seq } => {
+            let client = ClientInfo {
+                conn_id: conn.id,
+                user_id: conn.user_id,
+                tx: conn.tx.clone(),
+            };
+            gm.resume(client, room_id, last_seq).await?;
+            conn.rooms.insert(room_id);
+            Ok(None)
+        }
+        ClientCommand::Act { room_id, action } => {
+            gm.handle_action(conn.user_id, room_id, action).await?;
+            Ok(None)
+        }
+        ClientCommand::SitOut {
+            room_id,
+            sitting_out,
+        } => {
+            gm.set_sitting_out(conn.user_id, room_id, sitting_out)
+                .await?;
+            Ok(Some(ServerEvent::SittingOut {
+                room_id,
+                sitting_out,
+            }))
+        }
+        ClientCommand::SetClientSeed { room_id, seed } => {
+            let next_seed_hash = gm.set_client_seed(conn.user_id, room_id, &seed).await?;
+            Ok(Some(ServerEvent::ClientSeedSet {
+                room_id,
+                next_seed_hash,
+            }))
+        }
+        ClientCommand::AddChips { room_id, amount } => {
+            let chips = gm.add_chips(conn.user_id, room_id, amount).await?;
+            Ok(Some(ServerEvent::ChipsAdded { room_id, chips }))
+        }
+        ClientCommand::SubscribeLobby => {
+            gm.lobby_subscribers.insert(conn.id, conn.tx.clone());
+            let tables = open_tables(gm).await?;
+            Ok(Some(ServerEvent::Lobby { tables }))
+        }
+        ClientCommand::UnsubscribeLobby => {
+            gm.lobby_subscribers.remove(&conn.id);
+            Ok(Some(ServerEvent::LobbyUnsubscribed))
+        }
+        ClientCommand::PreAction {
+            room_id,
+            pre_action,
+        } => {
+            gm.set_pre_action(conn.user_id, room_id, pre_action).await?;
+            Ok(Some(ServerEvent::PreActionSet {
+                room_id,
+                pre_action,
+            }))
+        }
+        ClientCommand::Chat { room_id, message } => {
+            if !conn.rooms.contains(&room_id) {
+                return Err(anyhow::anyhow!("join the room before chatting"));
+            }
+            let message = message.trim();
+            if message.is_empty() || message.chars().count() > MAX_CHAT_LEN {
+                return Err(anyhow::anyhow!(
+                    "chat messages are 1 to {} characters",
+                    MAX_CHAT_LEN
+                ));
+            }
+            let payload = serde_json::json!({
+                "user_id": conn.user_id,
+                "message": message,
+                "sent_at": Utc::now().timestamp_millis(),
+            });
+            gm.emit_events(room_id, "chat", payload, None).await?;
+            Ok(None)
+        }
+    }
+}
+
+fn unregister(gm: &GameManager, room_id: Uuid, conn_id: Uuid) {
+    // the guard has to go before remove_if locks the same shard
+    if let Some(mut clients) = gm.client_registry.get_mut(&room_id) {
+        clients.retain(|ci| ci.conn_id != conn_id);
+    }
+    gm.client_registry
+        .remove_if(&room_id, |_, clients| clients.is_empty());
+}
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS small_blind BIGINT NOT NULL DEFAULT 5;
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS big_blind BIGINT NOT NULL DEFAULT 10;
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS ante BIGINT NOT NULL DEFAULT 0;
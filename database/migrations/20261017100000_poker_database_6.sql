@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS variant TEXT NOT NULL DEFAULT 'holdem'; --GameVariant::as_str, holdem when left out
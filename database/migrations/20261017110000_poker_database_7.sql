@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS betting_limit TEXT; --no_limit | pot_limit | fixed_limit, null plays the variant's usual structure
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS raise_cap SMALLINT NOT NULL DEFAULT 4; --fixed limit only, bets and raises allowed per street
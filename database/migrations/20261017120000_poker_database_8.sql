@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE hands ADD COLUMN IF NOT EXISTS server_seed_hash TEXT; --sha256 of the server seed, published before the deal
+ALTER TABLE hands ADD COLUMN IF NOT EXISTS client_seeds JSONB; --[{"seat": 1, "seed": "..."}] in the order they were mixed in
+ALTER TABLE hands ADD COLUMN IF NOT EXISTS server_seed TEXT; --revealed once the hand is finished
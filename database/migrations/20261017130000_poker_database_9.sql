@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS debug BOOLEAN NOT NULL DEFAULT false; --allows stacked or seeded decks when the server runs with DEBUG_ROOMS
+ALTER TABLE hands ADD COLUMN IF NOT EXISTS deck_source TEXT NOT NULL DEFAULT 'secure'; --secure | seeded | stacked
+ALTER TABLE hands ADD COLUMN IF NOT EXISTS dealt_deck JSONB; --full deck order, only for stacked decks the seeds can't rebuild
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS action_clock_secs SMALLINT NOT NULL DEFAULT 30; --time to act before the time bank is used
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS time_bank_secs SMALLINT NOT NULL DEFAULT 60; --every player's time bank when full
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS time_bank_refill_hands SMALLINT NOT NULL DEFAULT 20; --time banks are full again every this many hands, 0 never
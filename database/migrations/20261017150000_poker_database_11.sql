@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS min_buy_in BIGINT NOT NULL DEFAULT 200; --chips a player sits down with at least
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS max_buy_in BIGINT NOT NULL DEFAULT 1000; --and at most
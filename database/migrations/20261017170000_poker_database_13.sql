@@ -0,0 +1,103 @@
+-- Add migration script here
+-- double-entry chip ledger. every chip sits in exactly one account, postings move chips between
+-- accounts and their entries always sum to zero. the issuance account goes negative by every chip
+-- handed out, so all balances together are zero as long as nothing was lost or made up
+CREATE TABLE IF NOT EXISTS ledger_accounts(
+    id uuid PRIMARY KEY DEFAULT uuid_generate_v4(),
+    kind TEXT NOT NULL, --issuance | wallet | escrow | rake | prize_pool
+    user_id uuid, --wallet and escrow, no foreign keys so the history outlives deleted users
+    room_id uuid, --escrow, the player's stack at that table
+    tournament_id uuid, --prize_pool
+    balance BIGINT NOT NULL DEFAULT 0, --kept in step with the entries by the posting
+    created_at timestamptz NOT NULL DEFAULT now(),
+    CONSTRAINT ledger_accounts_kind CHECK (kind IN ('issuance', 'wallet', 'escrow', 'rake', 'prize_pool')),
+    CONSTRAINT ledger_accounts_no_overdraft CHECK (kind = 'issuance' OR balance >= 0),
+    CONSTRAINT ledger_accounts_owner UNIQUE NULLS NOT DISTINCT (kind, user_id, room_id, tournament_id)
+);
+
+CREATE TABLE IF NOT EXISTS ledger_postings(
+    id uuid PRIMARY KEY DEFAULT uuid_generate_v4(),
+    kind TEXT NOT NULL, --grant | buy_in | cash_out | pot_award | rake
+    room_id uuid,
+    hand_id uuid,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_ledger_postings_room ON ledger_postings(room_id, created_at);
+
+CREATE TABLE IF NOT EXISTS ledger_entries(
+    id BIGSERIAL PRIMARY KEY,
+    posting_id uuid NOT NULL REFERENCES ledger_postings(id),
+    account_id uuid NOT NULL REFERENCES ledger_accounts(id),
+    amount BIGINT NOT NULL CHECK (amount <> 0) --positive moves chips into the account
+);
+
+CREATE INDEX IF NOT EXISTS idx_ledger_entries_posting ON ledger_entries(posting_id);
+CREATE INDEX IF NOT EXISTS idx_ledger_entries_account ON ledger_entries(account_id);
+
+-- postings and entries are never changed, a mistake is undone by another posting
+CREATE OR REPLACE FUNCTION ledger_immutable() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION '% is append only', TG_TABLE_NAME;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS ledger_postings_immutable ON ledger_postings;
+CREATE TRIGGER ledger_postings_immutable BEFORE UPDATE OR DELETE ON ledger_postings
+    FOR EACH ROW EXECUTE FUNCTION ledger_immutable();
+
+DROP TRIGGER IF EXISTS ledger_entries_immutable ON ledger_entries;
+CREATE TRIGGER ledger_entries_immutable BEFORE UPDATE OR DELETE ON ledger_entries
+    FOR EACH ROW EXECUTE FUNCTION ledger_immutable();
+
+-- checked at commit, once every entry of the posting is in
+CREATE OR REPLACE FUNCTION ledger_posting_balanced() RETURNS trigger AS $$
+BEGIN
+    IF (SELECT SUM(amount) FROM ledger_entries WHERE posting_id = NEW.posting_id) <> 0 THEN
+        RAISE EXCEPTION 'posting % is not balanced', NEW.posting_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS ledger_entries_balanced ON ledger_entries;
+CREATE CONSTRAINT TRIGGER ledger_entries_balanced AFTER INSERT ON ledger_entries
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW EXECUTE FUNCTION ledger_posting_balanced();
+
+-- every account made so far opens its wallet with the starting balance new accounts get, and
+-- the stacks already at tables open their escrow accounts the same way
+DO $$
+DECLARE
+    issuance uuid;
+    grant_id uuid;
+    player record;
+    stack record;
+BEGIN
+    INSERT INTO ledger_accounts (kind) VALUES ('issuance')
+        ON CONFLICT ON CONSTRAINT ledger_accounts_owner DO NOTHING;
+    SELECT id INTO issuance FROM ledger_accounts WHERE kind = 'issuance';
+    FOR player IN SELECT id FROM users
+                  WHERE NOT EXISTS (SELECT 1 FROM ledger_accounts WHERE kind = 'wallet' AND user_id = users.id) LOOP
+        INSERT INTO ledger_postings (kind) VALUES ('grant') RETURNING id INTO grant_id;
+        INSERT INTO ledger_accounts (kind, user_id, balance) VALUES ('wallet', player.id, 10000);
+        INSERT INTO ledger_entries (posting_id, account_id, amount) VALUES
+            (grant_id, issuance, -10000),
+            (grant_id, (SELECT id FROM ledger_accounts WHERE kind = 'wallet' AND user_id = player.id), 10000);
+    END LOOP;
+    FOR stack IN SELECT user_id, room_id, SUM(chips) AS chips FROM room_players
+                 WHERE NOT EXISTS (SELECT 1 FROM ledger_accounts a WHERE a.kind = 'escrow'
+                                   AND a.user_id = room_players.user_id AND a.room_id = room_players.room_id)
+                 GROUP BY user_id, room_id HAVING SUM(chips) > 0 LOOP
+        INSERT INTO ledger_postings (kind, room_id) VALUES ('grant', stack.room_id) RETURNING id INTO grant_id;
+        INSERT INTO ledger_accounts (kind, user_id, room_id, balance)
+            VALUES ('escrow', stack.user_id, stack.room_id, stack.chips);
+        INSERT INTO ledger_entries (posting_id, account_id, amount) VALUES
+            (grant_id, issuance, -stack.chips),
+            (grant_id, (SELECT id FROM ledger_accounts
+                        WHERE kind = 'escrow' AND user_id = stack.user_id AND room_id = stack.room_id), stack.chips);
+    END LOOP;
+    UPDATE ledger_accounts SET balance = (SELECT -COALESCE(SUM(balance), 0) FROM ledger_accounts WHERE kind <> 'issuance')
+        WHERE id = issuance;
+END;
+$$;
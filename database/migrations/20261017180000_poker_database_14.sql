@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS rake_bps SMALLINT NOT NULL DEFAULT 0; --share of each contested pot the house takes, 500 is 5%
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS rake_caps BIGINT[] NOT NULL DEFAULT '{}'; --most rake per hand, heads-up first then one more player each, the last covers bigger hands. empty is no cap
+ALTER TABLE rooms ADD COLUMN IF NOT EXISTS no_flop_no_drop BOOLEAN NOT NULL DEFAULT true; --no rake on hands that end in the first betting round
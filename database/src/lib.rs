@@ -4,9 +4,9 @@ use tracing::info;
 
 pub mod models;
 
-pub async fn create_pool(database_url: &str) -> anyhow::Result<PgPool> {
+pub async fn create_pool(database_url: &str, max_connections: u32) -> anyhow::Result<PgPool> {
     let pool = PgPoolOptions::new()
-        .max_connections(20)
+        .max_connections(max_connections)
         .connect(database_url)
         .await
         .with_context(|| format!("Failed to connect to database {}", &database_url))?;
@@ -14,6 +14,11 @@ pub struct Hand {
     pub board: Option<serde_json::Value>,
     pub winner_user_id: Option<Uuid>,
     pub result: Option<serde_json::Value>,
+    pub server_seed_hash: Option<String>,
+    pub client_seeds: Option<serde_json::Value>,
+    pub server_seed: Option<String>,
+    pub deck_source: String,
+    pub dealt_deck: Option<serde_json::Value>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -21,16 +26,24 @@ pub async fn create_hand(
     pool: &PgPool,
     room_id: Option<Uuid>,
     started_at: Option<DateTime<Utc>>,
+    server_seed_hash: Option<&str>,
+    client_seeds: Option<serde_json::Value>,
+    deck_source: &str,
+    dealt_deck: Option<serde_json::Value>,
 ) -> anyhow::Result<Uuid> {
     let record = sqlx::query_as!(
         Hand,
         r#"
-        INSERT INTO hands (room_id , started_at)
-        VALUES ($1 ,$2)
-        RETURNING id , room_id , started_at , finished_at , pot , board , winner_user_id , result , created_at 
+        INSERT INTO hands (room_id , started_at , server_seed_hash , client_seeds , deck_source , dealt_deck)
+        VALUES ($1 ,$2 , $3 , $4 , $5 , $6)
+        RETURNING id , room_id , started_at , finished_at , pot , board , winner_user_id , result , server_seed_hash , client_seeds , server_seed , deck_source , dealt_deck , created_at 
         "#,
         room_id,
-        started_at
+        started_at,
+        server_seed_hash,
+        client_seeds,
+        deck_source,
+        dealt_deck
     ).fetch_one(pool).await?;
 
     Ok(record.id)
@@ -64,11 +77,26 @@ pub async fn finish_hand(
     Ok(())
 }
 
+pub async fn reveal_hand_seed(pool: &PgPool, hand_id: Uuid, server_seed: &str) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE hands SET server_seed = $2
+        WHERE id = $1
+        "#,
+        hand_id,
+        server_seed
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
 pub async fn find_by_id_hands(pool: &PgPool, id: Uuid) -> anyhow::Result<Option<Hand>> {
     let record = sqlx::query_as!(
         Hand,
         r#"
-        SELECT id, room_id, started_at, finished_at, pot, board, winner_user_id, result, created_at
+        SELECT id, room_id, started_at, finished_at, pot, board, winner_user_id, result, server_seed_hash, client_seeds, server_seed, deck_source, dealt_deck, created_at
         FROM hands
         WHERE id = $1
         "#,
@@ -87,7 +115,7 @@ pub async fn list_by_hands(
     let records = sqlx::query_as!(
         Hand,
         r#"
-        SELECT id, room_id, started_at, finished_at, pot, board, winner_user_id, result, created_at
+        SELECT id, room_id, started_at, finished_at, pot, board, winner_user_id, result, server_seed_hash, client_seeds, server_seed, deck_source, dealt_deck, created_at
         FROM hands
         WHERE id = $1
         ORDER BY created_at DESC
@@ -101,3 +129,70 @@ pub async fn list_by_hands(
 
     Ok(records)
 }
+
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow)]
+pub struct RoomHandStats {
+    pub room_id: Uuid,
+    pub hands: i64,
+    pub avg_pot: i64,
+}
+
+// hands finished since `since` per room, rooms without any are left out
+pub async fn hand_stats_by_room(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+    room_ids: &[Uuid],
+) -> anyhow::Result<Vec<RoomHandStats>> {
+    let records = sqlx::query_as!(
+        RoomHandStats,
+        r#"
+        SELECT room_id AS "room_id!", COUNT(*) AS "hands!", COALESCE(AVG(pot), 0)::BIGINT AS "avg_pot!"
+        FROM hands
+        WHERE finished_at >= $1 AND room_id = ANY($2)
+        GROUP BY room_id
+        "#,
+        since,
+        room_ids
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records)
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct RakeDay {
+    pub day: DateTime<Utc>,
+    pub hands: i64,
+    pub raked_hands: i64,
+    pub pot: i64,
+    pub rake: i64,
+}
+
+// what a room's hands paid the house per day since `since`, latest day first
+pub async fn rake_report(
+    pool: &PgPool,
+    room_id: Uuid,
+    since: DateTime<Utc>,
+) -> anyhow::Result<Vec<RakeDay>> {
+    let records = sqlx::query_as!(
+        RakeDay,
+        r#"
+        SELECT date_trunc('day', finished_at) AS "day!",
+            COUNT(*) AS "hands!",
+            COUNT(*) FILTER (WHERE (result->>'rake')::BIGINT > 0) AS "raked_hands!",
+            COALESCE(SUM(pot), 0)::BIGINT AS "pot!",
+            COALESCE(SUM((result->>'rake')::BIGINT), 0)::BIGINT AS "rake!"
+        FROM hands
+        WHERE room_id = $1 AND finished_at >= $2
+        GROUP BY 1
+        ORDER BY 1 DESC
+        "#,
+        room_id,
+        since
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records)
+}
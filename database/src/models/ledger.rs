@@ -0,0 +1,460 @@
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+pub const STARTING_BALANCE: i64 = 10000; // granted to every new wallet
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AccountKind {
+    Issuance, // where granted chips come from, its balance is minus every chip handed out
+    Wallet,
+    Escrow, // a player's stack at one table
+    Rake,
+    PrizePool,
+}
+
+impl AccountKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountKind::Issuance => "issuance",
+            AccountKind::Wallet => "wallet",
+            AccountKind::Escrow => "escrow",
+            AccountKind::Rake => "rake",
+            AccountKind::PrizePool => "prize_pool",
+        }
+    }
+}
+
+// an account is opened by the first posting that touches it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Account {
+    pub kind: AccountKind,
+    pub user_id: Option<Uuid>,
+    pub room_id: Option<Uuid>,
+    pub tournament_id: Option<Uuid>,
+}
+
+impl Account {
+    fn new(kind: AccountKind) -> Self {
+        Account {
+            kind,
+            user_id: None,
+            room_id: None,
+            tournament_id: None,
+        }
+    }
+
+    pub fn issuance() -> Self {
+        Account::new(AccountKind::Issuance)
+    }
+
+    pub fn wallet(user_id: Uuid) -> Self {
+        Account {
+            user_id: Some(user_id),
+            ..Account::new(AccountKind::Wallet)
+        }
+    }
+
+    pub fn escrow(user_id: Uuid, room_id: Uuid) -> Self {
+        Account {
+            user_id: Some(user_id),
+            room_id: Some(room_id),
+            ..Account::new(AccountKind::Escrow)
+        }
+    }
+
+    // the house's, rake from every table lands here
+    pub fn rake() -> Self {
+        Account::new(AccountKind::Rake)
+    }
+
+    pub fn prize_pool(tournament_id: Uuid) -> Self {
+        Account {
+            tournament_id: Some(tournament_id),
+            ..Account::new(AccountKind::PrizePool)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostingKind {
+    Grant,
+    BuyIn,
+    CashOut,
+    PotAward,
+    Rake,
+}
+
+impl PostingKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostingKind::Grant => "grant",
+            PostingKind::BuyIn => "buy_in",
+            PostingKind::CashOut => "cash_out",
+            PostingKind::PotAward => "pot_award",
+            PostingKind::Rake => "rake",
+        }
+    }
+}
+
+// what check_ledger found, every count is zero and total is zero when no chip was lost or made up
+#[derive(Debug, Clone, Serialize)]
+pub struct LedgerCheck {
+    pub total: i64,               // every entry ever posted, summed
+    pub unbalanced_postings: i64, // postings whose entries don't sum to zero
+    pub drifted_accounts: i64,    // accounts whose balance isn't the sum of their entries
+}
+
+impl LedgerCheck {
+    pub fn conserved(&self) -> bool {
+        self.total == 0 && self.unbalanced_postings == 0 && self.drifted_accounts == 0
+    }
+}
+
+// one posting in one transaction. positive amounts move chips into the account, and they have
+// to sum to zero. an account that would go below zero (anything but issuance) fails the posting
+pub async fn post(
+    pool: &PgPool,
+    kind: PostingKind,
+    room_id: Option<Uuid>,
+    hand_id: Option<Uuid>,
+    entries: &[(Account, i64)],
+) -> anyhow::Result<Uuid> {
+    let mut tx = pool.begin().await?;
+    let posting_id = post_in(&mut tx, kind, room_id, hand_id, entries).await?;
+    tx.commit().await?;
+    Ok(posting_id)
+}
+
+// a posting inside the caller's transaction, for postings that land together or not at all
+async fn post_in(
+    conn: &mut PgConnection,
+    kind: PostingKind,
+    room_id: Option<Uuid>,
+    hand_id: Option<Uuid>,
+    entries: &[(Account, i64)],
+) -> anyhow::Result<Uuid> {
+    if entries.iter().map(|&(_, amount)| amount).sum::<i64>() != 0 {
+        return Err(anyhow::anyhow!("{} posting is not balanced", kind.as_str()));
+    }
+    // the same account order everywhere so two postings never wait on each other
+    let mut entries = entries
+        .iter()
+        .filter(|&&(_, amount)| amount != 0)
+        .copied()
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|&(account, _)| account);
+
+    let posting = sqlx::query!(
+        r#"
+        INSERT INTO ledger_postings (kind, room_id, hand_id)
+        VALUES ($1, $2, $3)
+        RETURNING id
+        "#,
+        kind.as_str(),
+        room_id,
+        hand_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    for (account, amount) in entries {
+        // opened at zero first, a negative row would fail the overdraft check before any conflict
+        sqlx::query!(
+            r#"
+            INSERT INTO ledger_accounts (kind, user_id, room_id, tournament_id)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT ON CONSTRAINT ledger_accounts_owner DO NOTHING
+            "#,
+            account.kind.as_str(),
+            account.user_id,
+            account.room_id,
+            account.tournament_id
+        )
+        .execute(&mut *conn)
+        .await?;
+        let record = sqlx::query!(
+            r#"
+            UPDATE ledger_accounts SET balance = balance + $5
+            WHERE kind = $1
+                AND user_id IS NOT DISTINCT FROM $2
+                AND room_id IS NOT DISTINCT FROM $3
+                AND tournament_id IS NOT DISTINCT FROM $4
+            RETURNING id
+            "#,
+            account.kind.as_str(),
+            account.user_id,
+            account.room_id,
+            account.tournament_id,
+            amount
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO ledger_entries (posting_id, account_id, amount)
+            VALUES ($1, $2, $3)
+            "#,
+            posting.id,
+            record.id,
+            amount
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(posting.id)
+}
+
+fn is_overdraft(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<sqlx::Error>()
+        .and_then(|e| e.as_database_error())
+        .and_then(|e| e.constraint())
+        .is_some_and(|c| c == "ledger_accounts_no_overdraft")
+}
+
+// the chips a new account starts out with
+pub async fn grant_wallet(pool: &PgPool, user_id: Uuid, amount: i64) -> anyhow::Result<()> {
+    let entries = [
+        (Account::issuance(), -amount),
+        (Account::wallet(user_id), amount),
+    ];
+    post(pool, PostingKind::Grant, None, None, &entries).await?;
+    Ok(())
+}
+
+// wallet to the player's stack at the table, false when the wallet is too small
+pub async fn post_buy_in(
+    pool: &PgPool,
+    user_id: Uuid,
+    room_id: Uuid,
+    amount: i64,
+) -> anyhow::Result<bool> {
+    let entries = [
+        (Account::wallet(user_id), -amount),
+        (Account::escrow(user_id, room_id), amount),
+    ];
+    match post(pool, PostingKind::BuyIn, Some(room_id), None, &entries).await {
+        Ok(_) => Ok(true),
+        Err(e) if is_overdraft(&e) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn post_cash_out(
+    pool: &PgPool,
+    user_id: Uuid,
+    room_id: Uuid,
+    amount: i64,
+) -> anyhow::Result<()> {
+    let entries = [
+        (Account::escrow(user_id, room_id), -amount),
+        (Account::wallet(user_id), amount),
+    ];
+    post(pool, PostingKind::CashOut, Some(room_id), None, &entries).await?;
+    Ok(())
+}
+
+// what each player won or lost over the hand, chips won less chips put in, and the rake each
+// paid out of the pots they won. one posting each, in one transaction so neither lands alone
+pub async fn post_hand_result(
+    pool: &PgPool,
+    room_id: Uuid,
+    hand_id: Uuid,
+    nets: &[(Uuid, i64)],
+    rake_paid: &[(Uuid, i64)],
+) -> anyhow::Result<()> {
+    let award = nets
+        .iter()
+        .map(|&(user_id, net)| (Account::escrow(user_id, room_id), net))
+        .collect::<Vec<_>>();
+    let total_rake = rake_paid.iter().map(|&(_, rake)| rake).sum::<i64>();
+    let mut rake = rake_paid
+        .iter()
+        .map(|&(user_id, rake)| (Account::escrow(user_id, room_id), -rake))
+        .collect::<Vec<_>>();
+    rake.push((Account::rake(), total_rake));
+
+    let mut tx = pool.begin().await?;
+    if award.iter().any(|&(_, net)| net != 0) {
+        post_in(
+            &mut tx,
+            PostingKind::PotAward,
+            Some(room_id),
+            Some(hand_id),
+            &award,
+        )
+        .await?;
+    }
+    if total_rake != 0 {
+        post_in(
+            &mut tx,
+            PostingKind::Rake,
+            Some(room_id),
+            Some(hand_id),
+            &rake,
+        )
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+// zero for accounts nothing was posted to yet
+pub async fn account_balance(pool: &PgPool, account: Account) -> anyhow::Result<i64> {
+    let record = sqlx::query!(
+        r#"
+        SELECT balance FROM ledger_accounts
+        WHERE kind = $1
+            AND user_id IS NOT DISTINCT FROM $2
+            AND room_id IS NOT DISTINCT FROM $3
+            AND tournament_id IS NOT DISTINCT FROM $4
+        "#,
+        account.kind.as_str(),
+        account.user_id,
+        account.room_id,
+        account.tournament_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record.map_or(0, |r| r.balance))
+}
+
+pub async fn wallet_balance(pool: &PgPool, user_id: Uuid) -> anyhow::Result<i64> {
+    account_balance(pool, Account::wallet(user_id)).await
+}
+
+// proves chips are conserved: postings balance, balances match their entries, and since every
+// chip came out of issuance, everything sums to zero
+pub async fn check_ledger(pool: &PgPool) -> anyhow::Result<LedgerCheck> {
+    let record = sqlx::query_as!(
+        LedgerCheck,
+        r#"
+        SELECT
+            (SELECT COALESCE(SUM(amount), 0) FROM ledger_entries)::BIGINT AS "total!",
+            (SELECT COUNT(*) FROM (
+                SELECT posting_id FROM ledger_entries
+                GROUP BY posting_id
+                HAVING SUM(amount) <> 0
+            ) unbalanced) AS "unbalanced_postings!",
+            (SELECT COUNT(*) FROM ledger_accounts a
+                WHERE a.balance <> (
+                    SELECT COALESCE(SUM(e.amount), 0) FROM ledger_entries e WHERE e.account_id = a.id
+                )
+            ) AS "drifted_accounts!"
+        "#
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn seated(pool: &PgPool, user_id: Uuid, room_id: Uuid, buy_in: i64) {
+        grant_wallet(pool, user_id, STARTING_BALANCE).await.unwrap();
+        assert!(post_buy_in(pool, user_id, room_id, buy_in).await.unwrap());
+    }
+
+    async fn escrow(pool: &PgPool, user_id: Uuid, room_id: Uuid) -> i64 {
+        account_balance(pool, Account::escrow(user_id, room_id))
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn buy_in_and_cash_out(pool: PgPool) {
+        let (user, room) = (Uuid::new_v4(), Uuid::new_v4());
+        seated(&pool, user, room, 2000).await;
+        assert_eq!(wallet_balance(&pool, user).await.unwrap(), 8000);
+        assert_eq!(escrow(&pool, user, room).await, 2000);
+
+        post_cash_out(&pool, user, room, 2000).await.unwrap();
+        assert_eq!(wallet_balance(&pool, user).await.unwrap(), STARTING_BALANCE);
+        assert_eq!(escrow(&pool, user, room).await, 0);
+        assert!(check_ledger(&pool).await.unwrap().conserved());
+    }
+
+    #[sqlx::test]
+    async fn buy_in_above_the_wallet_posts_nothing(pool: PgPool) {
+        let (user, room) = (Uuid::new_v4(), Uuid::new_v4());
+        grant_wallet(&pool, user, 100).await.unwrap();
+        assert!(!post_buy_in(&pool, user, room, 101).await.unwrap());
+        assert_eq!(wallet_balance(&pool, user).await.unwrap(), 100);
+        assert_eq!(escrow(&pool, user, room).await, 0);
+        assert!(check_ledger(&pool).await.unwrap().conserved());
+    }
+
+    #[sqlx::test]
+    async fn unbalanced_postings_are_rejected(pool: PgPool) {
+        let user = Uuid::new_v4();
+        let entries = [(Account::issuance(), -10), (Account::wallet(user), 9)];
+        assert!(
+            post(&pool, PostingKind::Grant, None, None, &entries)
+                .await
+                .is_err()
+        );
+        assert_eq!(wallet_balance(&pool, user).await.unwrap(), 0);
+    }
+
+    #[sqlx::test]
+    async fn pot_award_and_rake_move_chips_between_stacks(pool: PgPool) {
+        let (a, b, room, hand) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        seated(&pool, a, room, 1000).await;
+        seated(&pool, b, room, 1000).await;
+        // a won the 300 b put in, then paid the rake out of the pot
+        post_hand_result(&pool, room, hand, &[(a, 300), (b, -300)], &[(a, 15)])
+            .await
+            .unwrap();
+        assert_eq!(escrow(&pool, a, room).await, 1285);
+        assert_eq!(escrow(&pool, b, room).await, 700);
+        assert_eq!(account_balance(&pool, Account::rake()).await.unwrap(), 15);
+        assert!(check_ledger(&pool).await.unwrap().conserved());
+    }
+
+    #[sqlx::test]
+    async fn stacks_cannot_go_negative(pool: PgPool) {
+        let (a, b, room) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        seated(&pool, a, room, 100).await;
+        seated(&pool, b, room, 100).await;
+        assert!(
+            post_hand_result(&pool, room, Uuid::new_v4(), &[(a, 150), (b, -150)], &[])
+                .await
+                .is_err()
+        );
+        assert!(post_cash_out(&pool, a, room, 101).await.is_err());
+        assert_eq!(escrow(&pool, a, room).await, 100);
+        assert_eq!(escrow(&pool, b, room).await, 100);
+        assert!(check_ledger(&pool).await.unwrap().conserved());
+    }
+
+    #[sqlx::test]
+    async fn an_award_does_not_land_without_its_rake(pool: PgPool) {
+        let (a, b, room, hand) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        seated(&pool, a, room, 100).await;
+        seated(&pool, b, room, 100).await;
+        // more rake than the winner holds after the award
+        assert!(
+            post_hand_result(&pool, room, hand, &[(a, 50), (b, -50)], &[(a, 200)])
+                .await
+                .is_err()
+        );
+        assert_eq!(escrow(&pool, a, room).await, 100);
+        assert_eq!(escrow(&pool, b, room).await, 100);
+        assert_eq!(account_balance(&pool, Account::rake()).await.unwrap(), 0);
+        assert!(check_ledger(&pool).await.unwrap().conserved());
+    }
+}
@@ -1,6 +1,7 @@
 pub mod actions;
 pub mod hand_players;
 pub mod hands;
+pub mod ledger;
 pub mod refresh_tokens;
 pub mod room_players;
 pub mod rooms;
@@ -10,6 +11,7 @@ pub mod users;
 pub use actions::*;
 pub use hand_players::*;
 pub use hands::*;
+pub use ledger::*;
 pub use refresh_tokens::*;
 pub use room_players::*;
 pub use rooms::*;
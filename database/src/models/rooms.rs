@@ -11,25 +11,73 @@ pub struct Rooms {
     pub host_user_id: Option<Uuid>,
     pub room_status: String,
     pub max_players: Option<i16>,
+    pub small_blind: i64,
+    pub big_blind: i64,
+    pub ante: i64,
+    pub variant: String,
+    pub betting_limit: Option<String>,
+    pub raise_cap: i16,
+    pub debug: bool,
+    pub action_clock_secs: i16,
+    pub time_bank_secs: i16,
+    pub time_bank_refill_hands: i16,
+    pub min_buy_in: i64,
+    pub max_buy_in: i64,
+    pub rake_bps: i16,
+    pub rake_caps: Vec<i64>,
+    pub no_flop_no_drop: bool,
     pub created_at: DateTime<Utc>,
 }
 
-pub async fn create_rooms(
-    pool: &PgPool,
-    room_name: Option<String>,
-    host_user_id: Option<Uuid>,
-    max_players: Option<i16>,
-) -> anyhow::Result<Uuid> {
+// columns a new room is created with, everything else takes its default
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NewRoom {
+    pub room_name: Option<String>,
+    pub host_user_id: Option<Uuid>,
+    pub max_players: Option<i16>,
+    pub small_blind: i64,
+    pub big_blind: i64,
+    pub ante: i64,
+    pub variant: String,
+    pub betting_limit: Option<String>,
+    pub raise_cap: i16,
+    pub debug: bool,
+    pub action_clock_secs: i16,
+    pub time_bank_secs: i16,
+    pub time_bank_refill_hands: i16,
+    pub min_buy_in: i64,
+    pub max_buy_in: i64,
+    pub rake_bps: i16,
+    pub rake_caps: Vec<i64>,
+    pub no_flop_no_drop: bool,
+}
+
+pub async fn create_rooms(pool: &PgPool, room: &NewRoom) -> anyhow::Result<Uuid> {
     let record = sqlx::query_as!(
         Rooms,
         r#"
-        INSERT INTO rooms (room_name , host_user_id , max_players)
-        VALUES ($1 , $2 , $3)
-        RETURNING id , room_name , host_user_id , room_status , max_players , created_at
+        INSERT INTO rooms (room_name , host_user_id , max_players , small_blind , big_blind , ante , variant , betting_limit , raise_cap , debug , action_clock_secs , time_bank_secs , time_bank_refill_hands , min_buy_in , max_buy_in , rake_bps , rake_caps , no_flop_no_drop)
+        VALUES ($1 , $2 , $3 , $4 , $5 , $6 , $7 , $8 , $9 , $10 , $11 , $12 , $13 , $14 , $15 , $16 , $17 , $18)
+        RETURNING id , room_name , host_user_id , room_status , max_players , small_blind , big_blind , ante , variant , betting_limit , raise_cap , debug , action_clock_secs , time_bank_secs , time_bank_refill_hands , min_buy_in , max_buy_in , rake_bps , rake_caps , no_flop_no_drop , created_at
         "#,
-        room_name,
-        host_user_id,
-        max_players
+        room.room_name,
+        room.host_user_id,
+        room.max_players,
+        room.small_blind,
+        room.big_blind,
+        room.ante,
+        room.variant,
+        room.betting_limit,
+        room.raise_cap,
+        room.debug,
+        room.action_clock_secs,
+        room.time_bank_secs,
+        room.time_bank_refill_hands,
+        room.min_buy_in,
+        room.max_buy_in,
+        room.rake_bps,
+        &room.rake_caps,
+        room.no_flop_no_drop
     )
     .fetch_one(pool)
     .await?;
@@ -40,7 +88,7 @@ pub async fn find_by_id_rooms(pool: &PgPool, id: Uuid) -> anyhow::Result<Option<
     let record = sqlx::query_as!(
         Rooms,
         r#"
-        SELECT id , room_name , host_user_id , room_status , max_players , created_at
+        SELECT id , room_name , host_user_id , room_status , max_players , small_blind , big_blind , ante , variant , betting_limit , raise_cap , debug , action_clock_secs , time_bank_secs , time_bank_refill_hands , min_buy_in , max_buy_in , rake_bps , rake_caps , no_flop_no_drop , created_at
         FROM rooms
         WHERE id = $1
         "#,
@@ -56,7 +104,7 @@ pub async fn list_by_status(pool: &PgPool, room_status: &str) -> anyhow::Result<
     let record = sqlx::query_as!(
         Rooms,
         r#"
-        SELECT id , room_name , host_user_id , room_status , max_players , created_at
+        SELECT id , room_name , host_user_id , room_status , max_players , small_blind , big_blind , ante , variant , betting_limit , raise_cap , debug , action_clock_secs , time_bank_secs , time_bank_refill_hands , min_buy_in , max_buy_in , rake_bps , rake_caps , no_flop_no_drop , created_at
         FROM rooms
         WHERE room_status = $1
         ORDER BY created_at DESC
@@ -69,6 +117,40 @@ pub async fn list_by_status(pool: &PgPool, room_status: &str) -> anyhow::Result<
     Ok(record)
 }
 
+// tables players can still sit down at, newest first
+pub async fn list_open_rooms(pool: &PgPool, limit: i64, offset: i64) -> anyhow::Result<Vec<Rooms>> {
+    let record = sqlx::query_as!(
+        Rooms,
+        r#"
+        SELECT id , room_name , host_user_id , room_status , max_players , small_blind , big_blind , ante , variant , betting_limit , raise_cap , debug , action_clock_secs , time_bank_secs , time_bank_refill_hands , min_buy_in , max_buy_in , rake_bps , rake_caps , no_flop_no_drop , created_at
+        FROM rooms
+        WHERE room_status <> 'finished'
+        ORDER BY created_at DESC
+        LIMIT $1 OFFSET $2
+        "#,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(record)
+}
+
+pub async fn count_open_rooms(pool: &PgPool) -> anyhow::Result<i64> {
+    let record = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM rooms
+        WHERE room_status <> 'finished'
+        "#
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(record.count)
+}
+
 pub async fn update_rooms(pool: &PgPool, id: Uuid, room_status: &str) -> anyhow::Result<()> {
     sqlx::query!(
         r#"